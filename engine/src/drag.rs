@@ -0,0 +1,193 @@
+// Drag and drop that tells the game what happened. The game gives each
+// drawable it wants to be draggable a payload (a card, a unit, whatever it
+// needs to know about it), calls update once a frame instead of
+// check_and_handle_drag, and gets back events saying what was picked up, what
+// it's over, and where it was dropped. Whether a drop is allowed is up to the
+// game: update asks it, and a refused drop goes back where it started.
+//
+// Which drawables can be picked up and what they can be dropped on still comes
+// from their DraggableSnapType, same as check_and_handle_drag. The target a
+// drawable is picked up from doesn't count until the pointer has left it, so
+// lifting a card out of its slot isn't already "over" that slot, and letting
+// go without leaving it just puts the card back.
+
+use crate::{coord_shift, DrawableId, FbCoords, MouseButton, State};
+use std::collections::HashMap;
+
+// What a drag did this frame. Every event carries the dragged drawable's id
+// and its payload.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DragEvent<P> {
+    // picked up
+    Start(DrawableId, P),
+    // moved onto a drop target, or off every target (None)
+    Over(DrawableId, Option<DrawableId>, P),
+    // let go on the target, which the game accepted; it's snapped onto it
+    Drop(DrawableId, DrawableId, P),
+    // let go anywhere else, or the game refused the drop; it's back where it
+    // started. Also sent if the drawable is removed mid-drag.
+    Cancel(DrawableId, P),
+}
+
+pub struct DragDrop<P> {
+    payloads: HashMap<DrawableId, P>,
+    dragging: Option<(DrawableId, P)>,
+    over: Option<DrawableId>,
+    // the target it was picked up from, until the pointer leaves it
+    source: Option<DrawableId>,
+}
+
+impl<P> Default for DragDrop<P> {
+    fn default() -> DragDrop<P> {
+        DragDrop {
+            payloads: HashMap::new(),
+            dragging: None,
+            over: None,
+            source: None,
+        }
+    }
+}
+
+impl<P: Clone> DragDrop<P> {
+    pub fn new() -> DragDrop<P> {
+        DragDrop::default()
+    }
+
+    // Makes id draggable, as long as its DraggableSnapType says it is.
+    // Drawables without a payload stay put.
+    pub fn set_payload(&mut self, id: DrawableId, payload: P) {
+        self.payloads.insert(id, payload);
+    }
+
+    pub fn remove_payload(&mut self, id: DrawableId) -> Option<P> {
+        self.payloads.remove(&id)
+    }
+
+    pub fn payload(&self, id: DrawableId) -> Option<&P> {
+        self.payloads.get(&id)
+    }
+
+    // The drawable being dragged, if any
+    pub fn dragging(&self) -> Option<DrawableId> {
+        self.dragging.as_ref().map(|(id, _)| *id)
+    }
+
+    // The drop target under the dragged drawable, if any
+    pub fn over(&self) -> Option<DrawableId> {
+        self.over
+    }
+
+    // Picks up, moves and drops drawables with the left mouse button. accept
+    // is asked whether the payload's drawable (the first id) can be dropped on
+    // the target (the second); it's only called on release, with state as it
+    // is then.
+    pub fn update<F>(&mut self, state: &mut State, mut accept: F) -> Vec<DragEvent<P>>
+    where
+        F: FnMut(&State, DrawableId, DrawableId, &P) -> bool,
+    {
+        let mut events = vec![];
+
+        if self.dragging.is_none() && state.mouse_just_pressed(MouseButton::Left) {
+            // topmost first, and only if the game gave it a payload
+            let picked = state
+                .drawables
+                .draw_order(None)
+                .into_iter()
+                .rev()
+                .find(|&id| {
+                    let item = &state.drawables[id];
+                    item.contains(state.mouse_coords) && item.is_draggable()
+                })
+                .and_then(|id| self.payloads.get(&id).map(|payload| (id, payload.clone())));
+
+            if let Some((id, payload)) = picked {
+                state.drag_item_id = Some(id);
+                state.drag_item_initial_coords = Some(state.drawables[id].get_coords());
+                state.initial_mouse_down_coords = Some(state.mouse_coords);
+                events.push(DragEvent::Start(id, payload.clone()));
+                self.dragging = Some((id, payload));
+                self.over = None;
+                self.source = drop_target(state, id);
+            }
+        }
+
+        let (id, payload) = match self.dragging.clone() {
+            Some(dragging) => dragging,
+            None => return events,
+        };
+        let (coords, start) = match (
+            dragged_coords(state),
+            state.drag_item_initial_coords,
+            state.drawables.contains(id),
+        ) {
+            (Some(coords), Some(start), true) => (coords, start),
+            _ => {
+                // removed out from under us, so there's nothing left to drag
+                self.end_drag(state);
+                events.push(DragEvent::Cancel(id, payload));
+                return events;
+            }
+        };
+        state.drawables[id].move_to(coords);
+
+        let mouse = state.mouse_coords;
+        let on_source = |source| state.drawables.get(source).is_some_and(|s| s.contains(mouse));
+        if !self.source.is_some_and(on_source) {
+            self.source = None;
+        }
+        let target = drop_target(state, id).filter(|&target| Some(target) != self.source);
+        if target != self.over {
+            self.over = target;
+            events.push(DragEvent::Over(id, target, payload.clone()));
+        }
+
+        if !state.mouse_down(MouseButton::Left) {
+            match target {
+                Some(target) if accept(state, id, target, &payload) => {
+                    let snap = state.drawables[target].get_coords();
+                    state.drawables[id].move_to(snap);
+                    events.push(DragEvent::Drop(id, target, payload));
+                }
+                _ => {
+                    state.drawables[id].move_to(start);
+                    events.push(DragEvent::Cancel(id, payload));
+                }
+            }
+            self.end_drag(state);
+        }
+        events
+    }
+
+    fn end_drag(&mut self, state: &mut State) {
+        self.dragging = None;
+        self.over = None;
+        self.source = None;
+        state.drag_item_id = None;
+        state.drag_item_initial_coords = None;
+        state.initial_mouse_down_coords = None;
+    }
+}
+
+// Where the dragged drawable is now: where it started, moved as far as the
+// mouse has since the button went down.
+pub(crate) fn dragged_coords(state: &State) -> Option<FbCoords> {
+    let (initial_mouse_x, initial_mouse_y) = state.initial_mouse_down_coords?;
+    let x_shift = (state.mouse_coords.0 as i32) - (initial_mouse_x as i32);
+    let y_shift = (state.mouse_coords.1 as i32) - (initial_mouse_y as i32);
+    Some(coord_shift(state.drag_item_initial_coords?, (x_shift, y_shift)))
+}
+
+// The topmost drawable under the cursor that id can be dropped on.
+pub(crate) fn drop_target(state: &State, id: DrawableId) -> Option<DrawableId> {
+    let dragged = state.drawables.get(id)?;
+    state
+        .drawables
+        .draw_order(Some(id))
+        .into_iter()
+        .rev()
+        .filter(|&target| target != id)
+        .find(|&target| {
+            let item = &state.drawables[target];
+            item.contains(state.mouse_coords) && item.is_releasable(dragged)
+        })
+}
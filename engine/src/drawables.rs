@@ -0,0 +1,174 @@
+use crate::{paint_key, Drawable};
+use std::ops::{Index, IndexMut};
+
+// A handle to a drawable in State::drawables. It stays valid until that
+// drawable is removed, whatever else gets added or removed around it, and a
+// removed drawable's id never finds whatever takes its slot next.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DrawableId {
+    index: usize,
+    generation: u32,
+}
+
+#[derive(Clone)]
+struct Slot {
+    generation: u32,
+    value: Option<Drawable>,
+}
+
+// Every drawable in the frame, looked up by DrawableId. Slots get reused once
+// they're freed, with a new generation so old ids stop matching.
+//
+// Iteration (and so the tie-break in draw order) follows the order drawables
+// were inserted, like pushing onto a Vec did.
+#[derive(Clone, Default)]
+pub struct Drawables {
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+    // live slot indices, oldest first
+    order: Vec<usize>,
+}
+
+impl Drawables {
+    pub fn new() -> Drawables {
+        Drawables::default()
+    }
+
+    pub fn insert(&mut self, drawable: Drawable) -> DrawableId {
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index].value = Some(drawable);
+                index
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(drawable),
+                });
+                self.slots.len() - 1
+            }
+        };
+        self.order.push(index);
+        DrawableId {
+            index,
+            generation: self.slots[index].generation,
+        }
+    }
+
+    pub fn get(&self, id: DrawableId) -> Option<&Drawable> {
+        match self.slots.get(id.index) {
+            Some(slot) if slot.generation == id.generation => slot.value.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: DrawableId) -> Option<&mut Drawable> {
+        match self.slots.get_mut(id.index) {
+            Some(slot) if slot.generation == id.generation => slot.value.as_mut(),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, id: DrawableId) -> bool {
+        self.get(id).is_some()
+    }
+
+    // Swaps in a new drawable under the same id, keeping its place in the draw
+    // order. Returns the old one, or None (and drops drawable) if id is stale.
+    pub fn replace(&mut self, id: DrawableId, drawable: Drawable) -> Option<Drawable> {
+        self.get_mut(id).map(|old| std::mem::replace(old, drawable))
+    }
+
+    pub fn remove(&mut self, id: DrawableId) -> Option<Drawable> {
+        let slot = self.slots.get_mut(id.index)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+        // linear, but boards are a few hundred drawables at most
+        self.order.retain(|&i| i != id.index);
+        Some(value)
+    }
+
+    // Removes everything. Every id handed out so far goes stale.
+    pub fn clear(&mut self) {
+        for index in std::mem::take(&mut self.order) {
+            let slot = &mut self.slots[index];
+            slot.value = None;
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free.push(index);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    // Ids of every drawable, in insertion order.
+    pub fn ids(&self) -> impl Iterator<Item = DrawableId> + '_ {
+        self.order.iter().map(move |&index| DrawableId {
+            index,
+            generation: self.slots[index].generation,
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (DrawableId, &Drawable)> + '_ {
+        self.ids().map(move |id| (id, &self[id]))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Drawable> + '_ {
+        self.order.iter().filter_map(move |&index| self.slots[index].value.as_ref())
+    }
+
+    // Ids in the order they're painted, back to front, like draw_order. Hit
+    // testing walks this backwards, so whatever is drawn on top gets clicked.
+    pub fn draw_order(&self, lifted: Option<DrawableId>) -> Vec<DrawableId> {
+        let mut ids: Vec<DrawableId> = self.ids().collect();
+        // sort_by_key is stable
+        ids.sort_by_key(|&id| paint_key(&self[id], lifted == Some(id)));
+        ids
+    }
+}
+
+// Panics if id is stale; use get for ids that might be.
+impl Index<DrawableId> for Drawables {
+    type Output = Drawable;
+
+    fn index(&self, id: DrawableId) -> &Drawable {
+        self.get(id).expect("stale DrawableId")
+    }
+}
+
+impl IndexMut<DrawableId> for Drawables {
+    fn index_mut(&mut self, id: DrawableId) -> &mut Drawable {
+        self.get_mut(id).expect("stale DrawableId")
+    }
+}
+
+impl Extend<Drawable> for Drawables {
+    fn extend<I: IntoIterator<Item = Drawable>>(&mut self, iter: I) {
+        for drawable in iter {
+            self.insert(drawable);
+        }
+    }
+}
+
+impl FromIterator<Drawable> for Drawables {
+    fn from_iter<I: IntoIterator<Item = Drawable>>(iter: I) -> Drawables {
+        let mut drawables = Drawables::new();
+        drawables.extend(iter);
+        drawables
+    }
+}
+
+impl From<Vec<Drawable>> for Drawables {
+    fn from(drawables: Vec<Drawable>) -> Drawables {
+        drawables.into_iter().collect()
+    }
+}
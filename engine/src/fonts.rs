@@ -0,0 +1,161 @@
+use fontdue::{Font, FontSettings};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const FONT_DATA_ROBOTO: &[u8] = include_bytes!("../../resources/fonts/RobotoMono-Regular.ttf") as &[u8];
+const FONT_DATA_CARTER: &[u8] = include_bytes!("../../resources/fonts/CarterOne-Regular.ttf") as &[u8];
+
+// A handle to a font in a FontRegistry. It's the font's index in the registry,
+// which is also the font index fontdue's layout sees.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct FontId(pub(crate) usize);
+
+impl FontId {
+    // The fonts every registry starts with, in this order. The engine draws card
+    // text with CARD_BODY and CARD_TITLE; load over them by name to restyle cards.
+    pub const CARD_BODY: FontId = FontId(0);
+    pub const CARD_TITLE: FontId = FontId(1);
+    pub const GAME_TITLE: FontId = FontId(2);
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+// Every font the game can draw with, looked up by name when loading and by
+// FontId when drawing. Loading a name that's already taken replaces that font
+// and keeps its id, so drawables that use it pick up the new one; draw()
+// notices the generation changed and repaints them on the next frame.
+//
+// Characters a font doesn't have are drawn with the first of the fallback
+// fonts that does, so e.g. loading a Japanese font and adding it as a fallback
+// makes Japanese card names work in every font.
+pub struct FontRegistry {
+    fonts: Vec<Font>,
+    names: HashMap<String, FontId>,
+    fallbacks: Vec<FontId>,
+    generation: usize,
+}
+
+impl Default for FontRegistry {
+    fn default() -> FontRegistry {
+        FontRegistry::new()
+    }
+}
+
+impl FontRegistry {
+    // A registry with the engine's built-in fonts: "card_body" (Roboto Mono),
+    // "card_title" and "game_title" (both Carter One). Roboto Mono also covers
+    // Cyrillic and Greek, so it's the first fallback.
+    pub fn new() -> FontRegistry {
+        let mut registry = FontRegistry::empty();
+        let roboto = Font::from_bytes(FONT_DATA_ROBOTO, FontSettings::default()).unwrap();
+        let carter = Font::from_bytes(FONT_DATA_CARTER, FontSettings::default()).unwrap();
+        registry.insert("card_body", roboto);
+        registry.insert("card_title", carter.clone());
+        registry.insert("game_title", carter);
+        registry.add_fallback(FontId::CARD_BODY);
+        registry
+    }
+
+    // A registry with nothing in it. The FontId constants don't mean anything
+    // until three fonts have been loaded.
+    pub fn empty() -> FontRegistry {
+        FontRegistry {
+            fonts: vec![],
+            names: HashMap::new(),
+            fallbacks: vec![],
+            generation: 0,
+        }
+    }
+
+    // Loads a TTF or OTF file and registers it under name.
+    pub fn load<P: AsRef<Path>>(&mut self, name: &str, path: P) -> io::Result<FontId> {
+        let bytes = fs::read(path)?;
+        self.load_bytes(name, &bytes)
+    }
+
+    pub fn load_bytes(&mut self, name: &str, bytes: &[u8]) -> io::Result<FontId> {
+        let font = Font::from_bytes(bytes, FontSettings::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(self.insert(name, font))
+    }
+
+    pub fn insert(&mut self, name: &str, font: Font) -> FontId {
+        if let Some(&id) = self.names.get(name) {
+            self.fonts[id.0] = font;
+            self.generation += 1;
+            return id;
+        }
+        let id = FontId(self.fonts.len());
+        self.fonts.push(font);
+        self.names.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn get(&self, name: &str) -> Option<FontId> {
+        self.names.get(name).copied()
+    }
+
+    // Fallbacks are tried in the order they were added.
+    pub fn add_fallback(&mut self, id: FontId) {
+        if !self.fallbacks.contains(&id) {
+            self.fallbacks.push(id);
+            self.generation += 1;
+        }
+    }
+
+    pub fn clear_fallbacks(&mut self) {
+        self.fallbacks.clear();
+        self.generation += 1;
+    }
+
+    pub fn fallbacks(&self) -> &[FontId] {
+        &self.fallbacks
+    }
+
+    // Goes up whenever a font is replaced or the fallbacks change, i.e. when
+    // text that's already on screen might look different.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    // The font that actually draws c when text asks for font: font itself if it
+    // has the character, otherwise the first fallback that does. If none do,
+    // font draws its missing-glyph box.
+    pub fn font_for(&self, font: FontId, c: char) -> FontId {
+        // line breaks and such are never drawn, no point switching fonts for them
+        if c.is_control() || self.has_char(font, c) {
+            return font;
+        }
+        self.fallbacks
+            .iter()
+            .copied()
+            .find(|&fallback| self.has_char(fallback, c))
+            .unwrap_or(font)
+    }
+
+    pub fn has_char(&self, id: FontId, c: char) -> bool {
+        self.font(id).lookup_glyph_index(c) != 0
+    }
+
+    // Panics if id came from a different registry with more fonts in it.
+    pub fn font(&self, id: FontId) -> &Font {
+        &self.fonts[id.0]
+    }
+
+    // All the fonts, indexed by FontId::index.
+    pub fn as_slice(&self) -> &[Font] {
+        &self.fonts
+    }
+
+    pub fn len(&self) -> usize {
+        self.fonts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fonts.is_empty()
+    }
+}
@@ -0,0 +1,218 @@
+use crate::fonts::FontId;
+use crate::text::{Icon, LayoutKey};
+use crate::Color;
+use fontdue::{Font, Metrics};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Past this many entries a cache is dropped and rebuilt from scratch. Text that
+// changes every frame (timers, counters) would otherwise grow it forever.
+const MAX_GLYPHS: usize = 4096;
+const MAX_LAYOUTS: usize = 512;
+const MAX_ICONS: usize = 256;
+
+pub struct Glyph {
+    pub metrics: Metrics,
+    pub bitmap: Vec<u8>,
+}
+
+// One glyph of a laid out string, positioned relative to the corner of the
+// rect it was laid out in. font is from the registry the layout was made with;
+// color, bold and icon come from the spans covering it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PlacedGlyph {
+    pub c: char,
+    pub x: f32,
+    pub y: f32,
+    pub px: f32,
+    pub font: FontId,
+    pub color: Option<Color>,
+    pub bold: bool,
+    pub icon: Option<Icon>,
+}
+
+// Rasterized glyphs keyed by font, character and pixel size, plus (optionally)
+// whole layouts keyed by the string, its style and the box it was laid out in.
+// Icon bitmaps are kept the same way, keyed by icon and side length.
+// Rasterizing is most of the cost of drawing text, and a board of cards redraws
+// the same few hundred glyphs every frame.
+pub struct GlyphCache {
+    glyphs: HashMap<(usize, char, u32), Rc<Glyph>>,
+    layouts: HashMap<LayoutKey, Rc<Vec<PlacedGlyph>>>,
+    icons: HashMap<(Icon, usize), Rc<Vec<u8>>>,
+    pub cache_layouts: bool,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl Default for GlyphCache {
+    fn default() -> GlyphCache {
+        GlyphCache::new()
+    }
+}
+
+impl GlyphCache {
+    pub fn new() -> GlyphCache {
+        GlyphCache {
+            glyphs: HashMap::new(),
+            layouts: HashMap::new(),
+            icons: HashMap::new(),
+            cache_layouts: true,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn glyph(&mut self, font: &Font, c: char, size: f32) -> Rc<Glyph> {
+        let key = (font.file_hash(), c, size.to_bits());
+        if let Some(glyph) = self.glyphs.get(&key) {
+            self.hits += 1;
+            return glyph.clone();
+        }
+        self.misses += 1;
+        if self.glyphs.len() >= MAX_GLYPHS {
+            self.glyphs.clear();
+        }
+        let (metrics, bitmap) = font.rasterize(c, size);
+        let glyph = Rc::new(Glyph { metrics, bitmap });
+        self.glyphs.insert(key, glyph.clone());
+        glyph
+    }
+
+    // A side x side coverage bitmap of the icon, rasterized the first time
+    // it's drawn at that size.
+    pub fn icon(&mut self, icon: Icon, side: usize) -> Rc<Vec<u8>> {
+        if let Some(bitmap) = self.icons.get(&(icon, side)) {
+            return bitmap.clone();
+        }
+        if self.icons.len() >= MAX_ICONS {
+            self.icons.clear();
+        }
+        let bitmap = Rc::new(icon.rasterize(side));
+        self.icons.insert((icon, side), bitmap.clone());
+        bitmap
+    }
+
+    // Returns the cached layout for this key, or runs make_layout and remembers
+    // the result. With cache_layouts off it always runs make_layout.
+    pub(crate) fn layout<F: FnOnce() -> Vec<PlacedGlyph>>(
+        &mut self,
+        key: LayoutKey,
+        make_layout: F,
+    ) -> Rc<Vec<PlacedGlyph>> {
+        if !self.cache_layouts {
+            return Rc::new(make_layout());
+        }
+        if let Some(layout) = self.layouts.get(&key) {
+            return layout.clone();
+        }
+        if self.layouts.len() >= MAX_LAYOUTS {
+            self.layouts.clear();
+        }
+        let layout = Rc::new(make_layout());
+        self.layouts.insert(key, layout.clone());
+        layout
+    }
+
+    pub fn glyph_count(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn layout_count(&self) -> usize {
+        self.layouts.len()
+    }
+
+    pub fn icon_count(&self) -> usize {
+        self.icons.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.glyphs.clear();
+        self.layouts.clear();
+        self.icons.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{layout_text_bounds, FontRegistry, Rect, TextStyle};
+
+    fn lay_out(cache: &mut GlyphCache, fonts: &FontRegistry, s: &str) {
+        let r = Rect::new(0, 0, 400, 100);
+        layout_text_bounds(cache, s, r, fonts, FontId::CARD_BODY, 24.0, &TextStyle::default());
+    }
+
+    #[test]
+    fn repeated_text_hits_the_cache() {
+        let fonts = FontRegistry::new();
+        let mut cache = GlyphCache::new();
+
+        // "Knight" has six different letters
+        lay_out(&mut cache, &fonts, "Knight");
+        assert_eq!((cache.hits, cache.misses), (0, 6));
+        assert_eq!(cache.glyph_count(), 6);
+        assert_eq!(cache.layout_count(), 1);
+
+        lay_out(&mut cache, &fonts, "Knight");
+        assert_eq!((cache.hits, cache.misses), (6, 6));
+        assert_eq!(cache.glyph_count(), 6);
+        assert_eq!(cache.layout_count(), 1);
+
+        // new letters miss, the ones already seen don't
+        lay_out(&mut cache, &fonts, "Night");
+        assert_eq!((cache.hits, cache.misses), (10, 7));
+        assert_eq!(cache.layout_count(), 2);
+    }
+
+    #[test]
+    fn layouts_can_be_left_uncached() {
+        let fonts = FontRegistry::new();
+        let mut cache = GlyphCache::new();
+        cache.cache_layouts = false;
+
+        lay_out(&mut cache, &fonts, "Knight");
+        lay_out(&mut cache, &fonts, "Knight");
+        assert_eq!(cache.layout_count(), 0);
+        // glyphs are still kept
+        assert_eq!((cache.hits, cache.misses), (6, 6));
+    }
+
+    #[test]
+    fn full_caches_start_over() {
+        let fonts = FontRegistry::new();
+        let font = fonts.font(FontId::CARD_BODY);
+        let mut cache = GlyphCache::new();
+
+        // the same dot at slightly different sizes, so each one is a new entry
+        for i in 1..=MAX_GLYPHS {
+            cache.glyph(font, '.', 8.0 + i as f32 / 1024.0);
+        }
+        assert_eq!(cache.glyph_count(), MAX_GLYPHS);
+        cache.glyph(font, ',', 8.0);
+        assert_eq!(cache.glyph_count(), 1);
+        assert_eq!(cache.misses, MAX_GLYPHS + 1);
+
+        for i in 0..MAX_LAYOUTS {
+            lay_out(&mut cache, &fonts, &i.to_string());
+        }
+        assert_eq!(cache.layout_count(), MAX_LAYOUTS);
+        lay_out(&mut cache, &fonts, "one more");
+        assert_eq!(cache.layout_count(), 1);
+    }
+
+    #[test]
+    fn icons_are_rasterized_once_per_size() {
+        let mut cache = GlyphCache::new();
+        let first = cache.icon(Icon::Mana, 16);
+        assert_eq!(first.len(), 16 * 16);
+        assert!(Rc::ptr_eq(&first, &cache.icon(Icon::Mana, 16)));
+        assert_eq!(cache.icon_count(), 1);
+
+        cache.icon(Icon::Mana, 24);
+        cache.icon(Icon::Hp, 16);
+        assert_eq!(cache.icon_count(), 3);
+        cache.clear();
+        assert_eq!(cache.icon_count(), 0);
+    }
+}
@@ -0,0 +1,135 @@
+// Golden-image checks for the software rasterizer. A scene is rendered with the
+// headless backend and compared against a reference PNG checked into the repo.
+//
+// Set ENGINE_BLESS=1 to (re)write the references from the current output. A
+// missing reference is a failure otherwise, so a new case needs a blessed run
+// and its PNG committed alongside it.
+
+pub use crate::screenshot::load_png;
+use crate::screenshot::save_png;
+use crate::{draw, setup_headless_with, Color, Drawable, EngineConfig};
+use std::path::{Path, PathBuf};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ImageDiff {
+    pub mismatched_pixels: usize,
+    pub max_channel_delta: u8,
+}
+
+// A rendered frame and the resolution it was rendered at.
+pub struct Frame {
+    pub pixels: Vec<Color>,
+    pub width: usize,
+    pub height: usize,
+}
+
+// Renders the drawables exactly like a game frame would, minus the GPU.
+pub fn render_offscreen(drawables: Vec<Drawable>, bg_color: Color) -> Frame {
+    render_offscreen_with(EngineConfig::default(), drawables, bg_color)
+}
+
+pub fn render_offscreen_with(
+    config: EngineConfig,
+    drawables: Vec<Drawable>,
+    bg_color: Color,
+) -> Frame {
+    let mut state = setup_headless_with(config);
+    state.bg_color = bg_color;
+    state.drawables = drawables.into();
+    draw(&mut state);
+    Frame {
+        pixels: state.fb2d,
+        width: config.width,
+        height: config.height,
+    }
+}
+
+fn channel_delta(a: Color, b: Color) -> u8 {
+    // Alpha is ignored: PNG captures are RGB, same as what ends up on screen.
+    let d = |x: u8, y: u8| (x as i16 - y as i16).unsigned_abs() as u8;
+    d(a.0, b.0).max(d(a.1, b.1)).max(d(a.2, b.2))
+}
+
+// Counts pixels whose worst channel differs by more than tolerance.
+pub fn compare_images(actual: &[Color], expected: &[Color], tolerance: u8) -> ImageDiff {
+    assert_eq!(actual.len(), expected.len());
+    let mut diff = ImageDiff {
+        mismatched_pixels: 0,
+        max_channel_delta: 0,
+    };
+    for (&a, &e) in actual.iter().zip(expected.iter()) {
+        let delta = channel_delta(a, e);
+        diff.max_channel_delta = diff.max_channel_delta.max(delta);
+        if delta > tolerance {
+            diff.mismatched_pixels += 1;
+        }
+    }
+    diff
+}
+
+// Mismatches in solid red over a faded copy of the expected image, so it's
+// obvious where on the card things moved.
+pub fn diff_image(actual: &[Color], expected: &[Color], tolerance: u8) -> Vec<Color> {
+    actual
+        .iter()
+        .zip(expected.iter())
+        .map(|(&a, &e)| {
+            if channel_delta(a, e) > tolerance {
+                (255, 0, 0, 255)
+            } else {
+                let gray = ((e.0 as u16 + e.1 as u16 + e.2 as u16) / 3) as u8;
+                let faded = 192 + gray / 4;
+                (faded, faded, faded, 255)
+            }
+        })
+        .collect()
+}
+
+fn sibling(reference: &Path, suffix: &str) -> PathBuf {
+    let stem = reference.file_stem().unwrap().to_string_lossy();
+    reference.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+// Compares a full frame against dir/<name>.png. On failure the actual frame and
+// a diff image are written next to the reference before panicking.
+pub fn assert_golden<P: AsRef<Path>>(dir: P, name: &str, frame: &Frame, tolerance: u8) {
+    let fb = &frame.pixels;
+    let reference = dir.as_ref().join(format!("{}.png", name));
+
+    let bless = std::env::var("ENGINE_BLESS").as_deref() == Ok("1");
+    if bless {
+        std::fs::create_dir_all(dir.as_ref()).unwrap();
+        save_png(&reference, fb, frame.width, frame.height).unwrap();
+        return;
+    }
+    assert!(
+        reference.exists(),
+        "no golden image at {}; run with ENGINE_BLESS=1 to record it",
+        reference.display()
+    );
+
+    let (expected, w, h) = load_png(&reference).unwrap();
+    assert_eq!(
+        (w, h),
+        (frame.width, frame.height),
+        "golden image {} has the wrong size",
+        reference.display()
+    );
+
+    let diff = compare_images(fb, &expected, tolerance);
+    if diff.mismatched_pixels > 0 {
+        let actual_path = sibling(&reference, "actual");
+        let diff_path = sibling(&reference, "diff");
+        save_png(&actual_path, fb, frame.width, frame.height).unwrap();
+        let diff_pixels = diff_image(fb, &expected, tolerance);
+        save_png(&diff_path, &diff_pixels, frame.width, frame.height).unwrap();
+        panic!(
+            "{} pixels differ from {} (max channel delta {}, tolerance {}); see {}",
+            diff.mismatched_pixels,
+            reference.display(),
+            diff.max_channel_delta,
+            tolerance,
+            diff_path.display()
+        );
+    }
+}
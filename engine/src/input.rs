@@ -0,0 +1,202 @@
+// Named actions ("end_turn", "p1_play_1") bound to keys and mouse buttons, so
+// games ask whether an action happened instead of checking keycodes, and
+// players can rebind controls in a JSON file without recompiling:
+//
+//   {
+//     "end_turn": ["Space", "MouseRight"],
+//     "p1_play_1": ["Key1", "Numpad1"]
+//   }
+//
+// Keys use winit's VirtualKeyCode names; mouse buttons are MouseLeft,
+// MouseRight, MouseMiddle, or Mouse<n> for extra buttons.
+
+use crate::State;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use winit::event::{MouseButton, VirtualKeyCode};
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputMap {
+    actions: HashMap<String, Vec<Binding>>,
+}
+
+impl InputMap {
+    pub fn new() -> InputMap {
+        InputMap::default()
+    }
+
+    // Builder-style, for writing a game's default controls inline.
+    pub fn with(mut self, action: &str, bindings: &[Binding]) -> InputMap {
+        for &binding in bindings {
+            self.bind(action, binding);
+        }
+        self
+    }
+
+    // Adds a binding; an action can have as many as it likes.
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.actions.entry(action.to_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: &str) {
+        self.actions.remove(action);
+    }
+
+    // Empty for actions nothing is bound to.
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map(|b| b.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.actions.keys().map(|a| a.as_str())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<InputMap> {
+        InputMap::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(json: &str) -> io::Result<InputMap> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+        let file: HashMap<String, Vec<String>> =
+            serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+        let mut map = InputMap::new();
+        for (action, names) in file {
+            // an empty list still counts, it's how a file unbinds an action
+            map.actions.entry(action.clone()).or_default();
+            for name in names {
+                let binding = binding_from_name(&name)
+                    .ok_or_else(|| invalid(format!("{}: unknown key {:?}", action, name)))?;
+                map.bind(&action, binding);
+            }
+        }
+        Ok(map)
+    }
+
+    // Replaces the bindings of every action the file mentions and leaves the
+    // rest alone, so a controls file only needs the keys a player changed. A
+    // missing file changes nothing.
+    pub fn load_overrides<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let overrides = match InputMap::load(path) {
+            Ok(overrides) => overrides,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        self.actions.extend(overrides.actions);
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        // sorted, so saved files diff nicely
+        let file: BTreeMap<&str, Vec<String>> = self
+            .actions
+            .iter()
+            .map(|(action, bindings)| {
+                (action.as_str(), bindings.iter().map(|&b| binding_name(b)).collect())
+            })
+            .collect();
+        serde_json::to_string_pretty(&file).unwrap()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+}
+
+impl State {
+    // Whether any of the action's bindings is held this frame.
+    pub fn action_down(&self, action: &str) -> bool {
+        self.action_was_down(action, false)
+    }
+
+    // True on the one frame the action goes from up to down. Holding a second
+    // key for an action that's already down doesn't press it again.
+    pub fn action_just_pressed(&self, action: &str) -> bool {
+        self.action_was_down(action, false) && !self.action_was_down(action, true)
+    }
+
+    pub fn action_just_released(&self, action: &str) -> bool {
+        !self.action_was_down(action, false) && self.action_was_down(action, true)
+    }
+
+    fn action_was_down(&self, action: &str, prev: bool) -> bool {
+        self.input_map
+            .bindings(action)
+            .iter()
+            .any(|&binding| self.binding_down(binding, prev))
+    }
+
+    fn binding_down(&self, binding: Binding, prev: bool) -> bool {
+        match binding {
+            Binding::Key(key) => {
+                let keys = if prev { &self.prev_keys } else { &self.now_keys };
+                keys[key as usize]
+            }
+            Binding::Mouse(button) => {
+                if prev {
+                    self.mouse_was_down(button)
+                } else {
+                    self.mouse_down(button)
+                }
+            }
+        }
+    }
+}
+
+pub fn binding_from_name(name: &str) -> Option<Binding> {
+    let mouse = match name {
+        "MouseLeft" => Some(MouseButton::Left),
+        "MouseRight" => Some(MouseButton::Right),
+        "MouseMiddle" => Some(MouseButton::Middle),
+        _ => name.strip_prefix("Mouse").and_then(|n| n.parse().ok()).map(MouseButton::Other),
+    };
+    match mouse {
+        Some(button) => Some(Binding::Mouse(button)),
+        None => key_from_name(name).map(Binding::Key),
+    }
+}
+
+pub fn binding_name(binding: Binding) -> String {
+    match binding {
+        Binding::Key(key) => format!("{:?}", key),
+        Binding::Mouse(MouseButton::Left) => "MouseLeft".to_string(),
+        Binding::Mouse(MouseButton::Right) => "MouseRight".to_string(),
+        Binding::Mouse(MouseButton::Middle) => "MouseMiddle".to_string(),
+        Binding::Mouse(MouseButton::Other(n)) => format!("Mouse{}", n),
+    }
+}
+
+// winit can't turn names back into keys, so this lists the ones worth
+// binding. The names are the variant names, which is also what Debug prints.
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+            match name {
+                $(stringify!($key) => Some(VirtualKeyCode::$key),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+key_names!(
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Escape, Tab, Space, Return, Back, Insert, Delete, Home, End, PageUp, PageDown,
+    Left, Up, Right, Down,
+    LShift, RShift, LControl, RControl, LAlt, RAlt,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide, NumpadDecimal, NumpadEnter,
+    Apostrophe, Backslash, Comma, Equals, Grave, LBracket, Minus, Period, RBracket, Semicolon, Slash,
+);
@@ -9,60 +9,74 @@
 // notice may not be copied, modified, or distributed except
 // according to those terms.
 
-use fontdue::layout::{CoordinateSystem, Layout, LayoutSettings, TextStyle};
-use fontdue::Font;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::cmp::{max, min};
+use std::cmp::{max, min, Ordering};
 use std::fs::File;
 use std::io::Read;
 use std::num::Wrapping;
-use std::rc::Rc;
-use std::sync::Arc;
-use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, TypedBufferAccess};
-use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, SubpassContents};
-use vulkano::descriptor_set::PersistentDescriptorSet;
-use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
-use vulkano::device::{Device, DeviceExtensions, Features};
-use vulkano::format::Format;
-use vulkano::image::ImageCreateFlags;
-use vulkano::image::{
-    view::ImageView, ImageAccess, ImageDimensions, ImageUsage, StorageImage, SwapchainImage,
-};
-use vulkano::instance::Instance;
-use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
-use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
-use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
-use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
-use vulkano::render_pass::{Framebuffer, RenderPass, Subpass};
-use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
-use vulkano::shader::ShaderModule;
-use vulkano::swapchain::{self, AcquireError, Swapchain, SwapchainCreationError};
-use vulkano::sync::{self, FlushError, GpuFuture};
-use vulkano::Version;
-use vulkano_win::VkSurfaceBuild;
-pub use winit::event::{Event, VirtualKeyCode, WindowEvent};
+pub use winit::event::{Event, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
 pub use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::{Window, WindowBuilder};
 
 // We'll make our Color type an RGBA8888 pixel.
 pub type Color = (u8, u8, u8, u8);
 pub type FbCoords = (usize, usize);
-const WIDTH: usize = 1920;
-const HEIGHT: usize = 1080;
+pub const DEFAULT_WIDTH: usize = 1920;
+pub const DEFAULT_HEIGHT: usize = 1080;
 const FONT_SIZE: f32 = 4.0;
 const CARD_COLOR: Color = (0,0,0,255);
-const FONT_DATA_ROBOTO: &[u8] = include_bytes!("../../resources/fonts/RobotoMono-Regular.ttf") as &[u8];
-const FONT_DATA_CARTER: &[u8] = include_bytes!("../../resources/fonts/CarterOne-Regular.ttf") as &[u8];
-
-
-#[derive(Default, Debug, Clone)]
-struct Vertex {
-    position: [f32; 2],
-    uv: [f32; 2],
-}
-vulkano::impl_vertex!(Vertex, position, uv);
+// card text that no span gives a size to
+const CARD_TEXT_SIZE: f32 = 12.0;
+const CARD_TITLE_SIZE: f32 = 16.0;
+const CARD_MIN_TEXT_SIZE: f32 = 7.0;
+
+pub mod golden;
+mod renderer;
+pub use renderer::{HeadlessRenderer, Renderer, VulkanRenderer};
+mod screenshot;
+pub use screenshot::{encode_png, save_png, write_png};
+mod raster;
+pub use raster::{blend, clear, with_coverage, Canvas};
+use raster::{fill_rect, rect_outlined, rectangle};
+mod fonts;
+pub use fonts::{FontId, FontRegistry};
+mod glyph_cache;
+pub use glyph_cache::{Glyph, GlyphCache, PlacedGlyph};
+mod text;
+pub use text::{
+    draw_layout_text, draw_text, layout_text, layout_text_bounds, line_ranges, measure_text,
+    render_character, render_styled_character, HAlign, Icon, TextFit, TextMetrics, TextSpan,
+    TextStyle, VAlign, Wrap,
+};
+mod markup;
+pub use markup::{escape_markup, parse_markup, Markup, ICON_PLACEHOLDER};
+mod scene;
+pub use scene::Scene;
+
+mod drawables;
+pub use drawables::{DrawableId, Drawables};
+
+mod input;
+pub use input::{binding_from_name, binding_name, Binding, InputMap};
+
+mod mouse;
+pub use mouse::{mouse_button_index, update_hover, HoverEvent, MOUSE_BUTTONS};
+mod replay;
+pub use replay::{begin_frame, FrameInput, Recorder, Recording, Replay};
+mod drag;
+pub use drag::{DragDrop, DragEvent};
+use drag::{dragged_coords, drop_target};
+mod ui;
+pub use ui::{Response, Ui, UiStyle};
+mod sprite;
+pub use sprite::{draw_sprite, Image, Sprite};
+mod shapes;
+pub use shapes::{
+    circle_rect, draw_ellipse, draw_line, draw_polygon, draw_rounded_rect, ShapeStyle,
+};
+use shapes::Shape;
 
 #[allow(non_snake_case)]
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -105,41 +119,53 @@ impl Card {
     }
 
     pub fn get_description(&self) -> String {
-        //for rendering the card itself
-        let name = &self.name;
+        //for rendering the card itself, in markup (see card_text_style)
+        let name = escape_markup(&self.name);
 
         let stats = format!(
-            "HP:{} | AC:{} | Upkeep: {} \n {}",
-            self.health, self.defense, self.passiveCost, self.specialAttribute
+            "[hp] {}  [def] {}  Upkeep [mana] {}\n{}",
+            self.health,
+            self.defense,
+            self.passiveCost,
+            escape_markup(&self.specialAttribute)
         );
 
-        let attack_block = format!("ATK: {} \n {}", self.attack, self.attackTag);
+        let attack_block = format!("[atk] {}\n{}", self.attack, escape_markup(&self.attackTag));
 
         let special_block = format!(
-            "Special | Cost: {} \n {}",
-            self.specialCost, self.specialTag
+            "[b]Special[/b]  [mana] {}\n{}",
+            self.specialCost,
+            escape_markup(&self.specialTag)
         );
 
         format!(
-            "{} \n \n {} \n \n {} \n\n {}",
+            "{}\n\n{}\n\n{}\n\n{}",
             name, stats, attack_block, special_block
         )
     }
 
     pub fn get_clash_description(&self) -> String {
-        let name = &self.name;
+        self.get_clash_description_with_hp(self.health)
+    }
 
-        let stats = format!(
-            "HP:{} | Cost: {} \n",
-            self.health, self.playCost
-        );
+    // The clash description for a unit that's down to (or buffed up to) hp,
+    // shown red when it's hurt and green when it's above the card's health.
+    pub fn get_clash_description_with_hp(&self, hp: usize) -> String {
+        let name = escape_markup(&self.name);
 
-        let attack_block = format!("ATK: {} ATK Spd: {}", self.attack, self.attackSpeed);
+        let hp = match hp.cmp(&self.health) {
+            Ordering::Less => format!("[color=red]{}[/color]", hp),
+            Ordering::Greater => format!("[color=green]{}[/color]", hp),
+            Ordering::Equal => hp.to_string(),
+        };
+        let stats = format!("[hp] {}  Cost [mana] {}", hp, self.playCost);
+
+        let attack_block = format!("[atk] {}  ATK Spd: {}", self.attack, self.attackSpeed);
 
         let mov_block = format!("MOV Spd: {}", self.speed);
 
         format!(
-            "{} \n \n {}  \n\n {} \n \n {}",
+            "{}\n\n{}\n\n{}\n\n{}",
             name, stats, attack_block, mov_block
         )
     }
@@ -197,6 +223,17 @@ impl Unit {
         }
     }
 
+    pub fn get_clash_drawable(&self) -> Drawable {
+        let pc = &self.played_card;
+        Drawable::Text(
+            pc.rect,
+            pc.card.get_clash_description_with_hp(self.hp),
+            FontId::CARD_TITLE,
+            20.0,
+            clash_text_style(),
+        )
+    }
+
     pub fn assign_new_time(&self, time: std::time::Instant) -> Unit {
         Unit {
             played_card: self.played_card.move_pc(0),
@@ -206,18 +243,45 @@ impl Unit {
     }
 }
 
+// Centered card text with the name (the first line) set as a title. Wordy cards
+// shrink to fit rather than running off the bottom. description is markup, and
+// the title span indexes the text it parses to.
+pub fn card_text_style(description: &str) -> TextStyle {
+    let title = line_ranges(&parse_markup(description).text).remove(0);
+    TextStyle::default()
+        .aligned(HAlign::Center, VAlign::Top)
+        .with_span(title, Some(CARD_TITLE_SIZE), Some(FontId::CARD_TITLE))
+        .fit_to_rect(CARD_MIN_TEXT_SIZE)
+        .with_markup()
+}
+
+fn clash_text_style() -> TextStyle {
+    TextStyle::default()
+        .aligned(HAlign::Center, VAlign::Top)
+        .with_markup()
+}
+
 impl PlayedCard {
+    // Cards go on their own layer, above the slots they sit in.
     pub fn get_drawable(&self) -> Drawable {
         Drawable::PlayedCard(
             self.rect,
             self.card.get_description(),
             CARD_COLOR,
             Some(DraggableSnapType::Card(true, false)),
+            card_text_style(&self.card.get_description()),
         )
+        .on_layer(Layer::Cards, 0)
     }
 
     pub fn get_clash_drawable(&self) -> Drawable {
-        Drawable::Text(self.rect, self.card.get_clash_description(), FontFamily::CardTitle, 20.0)
+        Drawable::Text(
+            self.rect,
+            self.card.get_clash_description(),
+            FontId::CARD_TITLE,
+            20.0,
+            clash_text_style(),
+        )
     }
     pub fn get_drawable_rect(&self, c: Color) -> Drawable {
         Drawable::Rectangle(self.rect, c, Some(DraggableSnapType::Card(false, false)))
@@ -292,8 +356,12 @@ impl Deck {
     }
 
     pub fn shuffle(self: &mut Deck) {
+        self.shuffle_with(&mut rand::thread_rng());
+    }
+
+    // Shuffles using the given rng, e.g. state.rng so replays deal the same cards
+    pub fn shuffle_with<R: Rng + ?Sized>(self: &mut Deck, random_generator: &mut R) {
         let deck_size = self.cards.len();
-        let mut random_generator = rand::thread_rng();
 
         (0..(deck_size - 1)).for_each(|range_min_index| {
             let next_index = random_generator.gen_range(range_min_index..deck_size);
@@ -319,43 +387,117 @@ pub fn load_cards_from_file(file_path: &str) -> Deck {
     // cards.push(c);
 }
 
+// Settings chosen once at setup time. width and height are the logical
+// resolution of fb2d; the window can be any size and just scales it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EngineConfig {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Default for EngineConfig {
+    fn default() -> EngineConfig {
+        EngineConfig {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+        }
+    }
+}
+
+impl EngineConfig {
+    pub fn new(width: usize, height: usize) -> EngineConfig {
+        EngineConfig { width, height }
+    }
+
+    // Reads ENGINE_RESOLUTION=<width>x<height> (e.g. 1280x720), otherwise the
+    // default. A value that doesn't parse is reported and ignored.
+    pub fn from_env() -> EngineConfig {
+        let res = match std::env::var("ENGINE_RESOLUTION") {
+            Ok(res) => res,
+            Err(_) => return EngineConfig::default(),
+        };
+        EngineConfig::parse(&res).unwrap_or_else(|| {
+            let default = EngineConfig::default();
+            println!(
+                "Ignoring ENGINE_RESOLUTION={:?}, not <width>x<height> above zero; using {}x{}",
+                res, default.width, default.height
+            );
+            default
+        })
+    }
+
+    // <width>x<height>, both above zero since there'd be no framebuffer to draw into.
+    pub fn parse(res: &str) -> Option<EngineConfig> {
+        let (w, h) = res.split_once('x')?;
+        let config = EngineConfig::new(w.trim().parse().ok()?, h.trim().parse().ok()?);
+        (config.width > 0 && config.height > 0).then_some(config)
+    }
+}
+
 pub struct State {
+    pub config: EngineConfig,
     pub fb2d: Vec<(u8, u8, u8, u8)>,
-    pub drawables: Vec<Drawable>,
+    pub drawables: Drawables,
     pub p1_units: Vec<Unit>,
     pub p2_units: Vec<Unit>,
     pub bg_color: Color,
-    previous_frame_end: std::option::Option<std::boxed::Box<dyn vulkano::sync::GpuFuture>>,
-    recreate_swapchain: bool,
-    pub event_loop: EventLoop<()>,
-    fb2d_buffer: Arc<vulkano::buffer::CpuAccessibleBuffer<[(u8, u8, u8, u8)]>>,
+    // None for headless states, which have no window to pump events for
+    pub event_loop: Option<EventLoop<()>>,
+    pub renderer: Box<dyn Renderer>,
     pub now_keys: [bool; 255],
     pub prev_keys: [bool; 255],
-    fb2d_image: std::sync::Arc<vulkano::image::StorageImage>,
-    queue: std::sync::Arc<vulkano::device::Queue>,
-    swapchain: std::sync::Arc<vulkano::swapchain::Swapchain<winit::window::Window>>,
-    viewport: Viewport,
-    framebuffers: Vec<Arc<Framebuffer>>,
-    pipeline: Arc<GraphicsPipeline>,
-    render_pass: Arc<RenderPass>,
-    dimensions: ImageDimensions,
-    vs: Arc<ShaderModule>,
-    fs: Arc<ShaderModule>,
-    surface: std::sync::Arc<vulkano::swapchain::Surface<winit::window::Window>>,
-    device: Arc<vulkano::device::Device>,
-    set: std::sync::Arc<vulkano::descriptor_set::PersistentDescriptorSet>,
-    vertex_buffer: Arc<CpuAccessibleBuffer<[Vertex]>>,
+    // what the game's actions are bound to; see action_just_pressed
+    pub input_map: InputMap,
     pub window_width: f64,
     pub window_height: f64,
-    pub left_mouse_down: bool,
-    pub prev_left_mouse_down: bool,
+    // indexed by mouse_button_index, like now_keys
+    pub now_mouse_buttons: [bool; MOUSE_BUTTONS],
+    pub prev_mouse_buttons: [bool; MOUSE_BUTTONS],
+    // lines scrolled this frame; positive y is scrolling up
+    pub wheel_delta: (f32, f32),
     pub mouse_coords: FbCoords,
     pub prev_mouse_coords: FbCoords,
+    // the topmost drawable under the cursor as of the last update_hover
+    pub hovered: Option<DrawableId>,
+    // what the cursor moved onto and off of this frame, in order
+    pub hover_events: Vec<HoverEvent>,
     pub initial_mouse_down_coords: Option<FbCoords>,
-    pub drag_item_id: Option<usize>,
+    pub drag_item_id: Option<DrawableId>,
     pub drag_item_initial_coords: Option<FbCoords>,
-    pub card_body_font: Font,
-    pub game_title_font: Font
+    pub fonts: FontRegistry,
+    pub glyph_cache: GlyphCache,
+    // what's currently in fb2d, used by draw() to only repaint what changed
+    pub scene: Scene,
+    // Pressing this key saves the current frame into screenshot_dir; None disables it
+    pub screenshot_key: Option<VirtualKeyCode>,
+    pub screenshot_dir: std::path::PathBuf,
+    // Randomness for anything that should come out the same on replay; seed
+    // is what it was last seeded with
+    pub seed: u64,
+    pub rng: StdRng,
+    // frames started so far, and how long the current one is
+    pub frame: u64,
+    pub frame_time: std::time::Duration,
+    // game time: the sum of every frame_time, which replays reproduce exactly
+    pub clock: std::time::Duration,
+    pub started_at: std::time::Instant,
+    pub last_frame_at: Option<std::time::Instant>,
+    pub recorder: Option<Recorder>,
+    pub replay: Option<Replay>,
+}
+
+impl State {
+    pub fn width(&self) -> usize {
+        self.config.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.config.height
+    }
+
+    pub fn screen_size(&self) -> (usize, usize) {
+        (self.config.width, self.config.height)
+    }
 }
 
 fn coord_shift(initial: FbCoords, shifter: (i32, i32)) -> FbCoords {
@@ -367,32 +509,44 @@ fn coord_shift(initial: FbCoords, shifter: (i32, i32)) -> FbCoords {
     )
 }
 
-pub fn calculate_card_spacer_width(card_size: (usize, usize), num_slots: usize) -> usize {
+pub fn calculate_card_spacer_width(
+    screen_size: (usize, usize),
+    card_size: (usize, usize),
+    num_slots: usize,
+) -> usize {
+    let (screen_width, _) = screen_size;
     let (card_width, _) = card_size;
-    let total_spacer_space = WIDTH - ((num_slots + 1) * card_width);
+    let total_spacer_space = screen_width - ((num_slots + 1) * card_width);
     total_spacer_space / (num_slots + 3)
 }
 
 pub fn calculate_deck_position(
+    screen_size: (usize, usize),
     card_size: (usize, usize),
     card_padding_bottom: usize,
     num_slots: usize,
 ) -> FbCoords {
-    let spacer_width = calculate_card_spacer_width(card_size, num_slots);
+    let spacer_width = calculate_card_spacer_width(screen_size, card_size, num_slots);
     let (card_width, card_height) = card_size;
     (
         (num_slots + 2) * spacer_width + num_slots * card_width,
-        HEIGHT - card_height - card_padding_bottom,
+        screen_size.1 - card_height - card_padding_bottom,
     )
 }
 
-pub fn calculate_slot_x(slot_num: usize, card_size: (usize, usize), num_slots: usize) -> usize {
+pub fn calculate_slot_x(
+    screen_size: (usize, usize),
+    slot_num: usize,
+    card_size: (usize, usize),
+    num_slots: usize,
+) -> usize {
     let (card_width, _) = card_size;
-    let spacer_width = calculate_card_spacer_width(card_size, num_slots);
+    let spacer_width = calculate_card_spacer_width(screen_size, card_size, num_slots);
     slot_num * spacer_width + (slot_num - 1) * card_width
 }
 
 pub fn calculate_slot_y(
+    screen_size: (usize, usize),
     is_top: bool,
     card_padding_bottom: usize,
     card_padding_top: usize,
@@ -401,11 +555,12 @@ pub fn calculate_slot_y(
     if is_top {
         card_padding_top
     } else {
-        HEIGHT - card_padding_bottom - card_size.1
+        screen_size.1 - card_padding_bottom - card_size.1
     }
 }
 
 pub fn get_slot_rect(
+    screen_size: (usize, usize),
     slot_num: usize,
     card_size: (usize, usize),
     num_slots: usize,
@@ -413,8 +568,14 @@ pub fn get_slot_rect(
     card_padding_top: usize,
     card_padding_bottom: usize,
 ) -> Rect {
-    let slot_x = calculate_slot_x(slot_num, card_size, num_slots);
-    let slot_y = calculate_slot_y(is_top, card_padding_bottom, card_padding_top, card_size);
+    let slot_x = calculate_slot_x(screen_size, slot_num, card_size, num_slots);
+    let slot_y = calculate_slot_y(
+        screen_size,
+        is_top,
+        card_padding_bottom,
+        card_padding_top,
+        card_size,
+    );
     let (card_width, card_height) = card_size;
     Rect {
         x: slot_x,
@@ -426,6 +587,7 @@ pub fn get_slot_rect(
 
 #[allow(clippy::too_many_arguments)]
 pub fn generate_battle_slots(
+    screen_size: (usize, usize),
     card_size: (usize, usize),
     card_padding_bottom: usize,
     card_padding_top: usize,
@@ -434,9 +596,9 @@ pub fn generate_battle_slots(
 ) -> Vec<Drawable> {
     let mut drawables: Vec<Drawable> = vec![];
 
-    let spacer_width = calculate_card_spacer_width(card_size, num_slots);
+    let spacer_width = calculate_card_spacer_width(screen_size, card_size, num_slots);
     let (card_width, card_height) = card_size;
-    assert!(card_width * (num_slots + 1) < WIDTH);
+    assert!(card_width * (num_slots + 1) < screen_size.0);
 
     let y = card_height + card_padding_top + card_padding_bottom;
 
@@ -475,6 +637,7 @@ pub fn generate_battle_slots(
 
 #[allow(clippy::too_many_arguments)]
 pub fn generate_deck_slots(
+    screen_size: (usize, usize),
     card_size: (usize, usize),
     card_padding_bottom: usize,
     card_padding_top: usize,
@@ -485,16 +648,17 @@ pub fn generate_deck_slots(
     spacer_background_color: Color,
     is_top: bool
 ) -> Vec<Drawable> {
+    let (screen_width, screen_height) = screen_size;
     let (card_width, card_height) = card_size;
-    assert!(card_width * (num_slots + 1) < WIDTH);
+    assert!(card_width * (num_slots + 1) < screen_width);
 
-    let spacer_width = calculate_card_spacer_width(card_size, num_slots); // 3 represents double space between last card and deck, plus space to right of deck
+    let spacer_width = calculate_card_spacer_width(screen_size, card_size, num_slots); // 3 represents double space between last card and deck, plus space to right of deck
 
     let top_container = Drawable::Rectangle(
         Rect {
             x: 0,
             y: 0,
-            w: WIDTH,
+            w: screen_width,
             h: card_height + card_padding_top + card_padding_bottom,
         },
         spacer_background_color,
@@ -504,8 +668,8 @@ pub fn generate_deck_slots(
     let bottom_container = Drawable::Rectangle(
         Rect {
             x: 0,
-            y: HEIGHT - card_height - (card_padding_bottom + card_padding_top),
-            w: WIDTH,
+            y: screen_height - card_height - (card_padding_bottom + card_padding_top),
+            w: screen_width,
             h: card_height + card_padding_top + card_padding_bottom,
         },
         spacer_background_color,
@@ -514,12 +678,19 @@ pub fn generate_deck_slots(
 
     let mut slot_drawables: Vec<Drawable> = vec![bottom_container];
     if is_top { slot_drawables.push(top_container) };
-    let card_y = calculate_slot_y(false, card_padding_bottom, card_padding_top, card_size);
+    let card_y = calculate_slot_y(
+        screen_size,
+        false,
+        card_padding_bottom,
+        card_padding_top,
+        card_size,
+    );
 
     (1..num_slots + 1).for_each(|slot| {
         let card_x = slot * spacer_width + (slot - 1) * card_width;
         let top_card_slot_background = Drawable::Rectangle(
             get_slot_rect(
+                screen_size,
                 slot,
                 card_size,
                 num_slots,
@@ -532,6 +703,7 @@ pub fn generate_deck_slots(
         );
         let top_card_slot_frame = Drawable::RectOutlined(
             get_slot_rect(
+                screen_size,
                 slot,
                 card_size,
                 num_slots,
@@ -545,6 +717,7 @@ pub fn generate_deck_slots(
 
         let bottom_card_slot_background = Drawable::Rectangle(
             get_slot_rect(
+                screen_size,
                 slot,
                 card_size,
                 num_slots,
@@ -557,6 +730,7 @@ pub fn generate_deck_slots(
         );
         let bottom_card_slot_frame = Drawable::RectOutlined(
             get_slot_rect(
+                screen_size,
                 slot,
                 card_size,
                 num_slots,
@@ -577,7 +751,8 @@ pub fn generate_deck_slots(
         slot_drawables.push(bottom_card_slot_frame);
     });
 
-    let deck_slot_x = calculate_deck_position(card_size, card_padding_bottom, num_slots).0;
+    let deck_slot_x =
+        calculate_deck_position(screen_size, card_size, card_padding_bottom, num_slots).0;
     let top_deck_slot_background = Drawable::Rectangle(
         Rect {
             x: deck_slot_x,
@@ -628,228 +803,50 @@ pub fn generate_deck_slots(
     slot_drawables
 }
 
-// fn print_to_file(c: char, )
-
-pub fn render_character(
-    c: char,
-    fb: &mut [Color],
-    x: usize,
-    y: usize,
-    size: f32,
-    font: &Font,
-) -> (usize, usize) {
-    // WINDOWS
-
-    //MAC
-    // let font = include_bytes!("../resources/fonts/RobotoMono-Regular.ttf") as &[u8];
-
-    let (metrics, bitmap) = font.rasterize(c, size);
-
-    let mut bitmap_rgb: Vec<(u8, u8, u8, u8)> = vec![];
-
-    // // to draw a char at x,y loc with height h and width w, we draw into the framebuffer
-    // // starting at y * WIDTH + x and go until (y + h) * WIDTH + x + W
-
-    for gray in bitmap {
-        bitmap_rgb.push((gray, gray, gray, 1));
-    }
-
-    let mut bit_iter = bitmap_rgb.iter();
-
-    for curr_y in (y)..(y + metrics.height) {
-        #[allow(clippy::needless_range_loop)]
-        for j in (curr_y * WIDTH + x)..(curr_y * WIDTH + x + metrics.width) {
-            let pixel = bit_iter.next().unwrap();
-
-            if pixel.0 == 0 {
-                //skip adding the background!
-                continue;
-            }
-
-            fb[j] = *pixel;
-        }
-    }
-
-    //return these for use in text
-    (metrics.width, metrics.height)
-}
-
-pub fn draw_text(fb: &mut [Color], s: String, r: Rect, size: f32, font: &Font) {
-    //TODO: I would ideally like it to be able to decide it's own size based on the space
-    //it has to fill
-
-    let mut x = r.x;
-    let mut y = r.y;
-    let hor_lim = r.x + r.w;
-    let ver_lim = r.y + r.h;
-    let mut avg_space = 0;
-
-    for word in s.split_whitespace() {
-        for c in word.chars() {
-            let (new_w, new_h) = render_character(c, fb, x, y, size, font);
-            avg_space = new_w;
-
-            x += new_w;
-
-            if c == '\n' {
-                y += avg_space;
-            }
-
-            if x >= hor_lim {
-                x = r.x;
-                y += new_h;
-            }
-
-            if y >= ver_lim {
-                //stop drawing - sucks to suck
-                return;
-            }
-        }
-
-        x += avg_space;
-    }
-}
-
-pub fn draw_layout_text(fb: &mut [Color], s: String, r: Rect, font: &Font, size: f32) {
-    let fonts = &[font]; //need to make a list
-
-    let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
-
-    let lay_settings = LayoutSettings {
-        x: r.x as f32,
-        y: r.y as f32,
-        max_width: Some(r.w as f32),
-        max_height: Some(r.y as f32),
-        horizontal_align: fontdue::layout::HorizontalAlign::Center,
-        vertical_align: fontdue::layout::VerticalAlign::Bottom,
-        wrap_style: fontdue::layout::WrapStyle::Word,
-        wrap_hard_breaks: true,
-    };
-
-    layout.reset(&lay_settings);
-
-    let strings = s.lines();
-
-    let mut all_chars = vec![];
-
-    for line in s.lines() {
-        let new_chars = line.chars();
-
-        for ch in new_chars {
-            all_chars.push(ch);
-        }
-    }
-
-    let mut subtitle = true;
-    for (idx, string) in strings.enumerate() {
-        if idx == 0 {
-            //Card title should be big
-
-            if size >= 40.0 {
-                //not a card
-                layout.append(fonts, &TextStyle::new(string, size, 0));
-            } else {
-                layout.append(fonts, &TextStyle::new(string, 16.0, 0));
-            }
-        } else {
-            let mut size = 14.0;
-            if subtitle {
-                size = 18.0;
-                subtitle = !subtitle;
-
-                //should normally be outside this if block
-            }
-            layout.append(fonts, &TextStyle::new(string, size, 0));
-        }
-    }
-
-    let glyphs = layout.glyphs();
-
-    // println!("{:?}", layout.glyphs());
-
-    //check lengths
-    let mut x: f32 = r.x as f32;
-
-    let mut y: f32 = r.y as f32;
-
-    for (idx, glyph) in glyphs.iter().enumerate() {
-        let c = glyph.parent;
-
-        let mut delta_x: f32 = 0.0;
-        let mut delta_y: f32 = 0.0;
-
-        if idx > 0 {
-            delta_x = glyph.x - glyphs[idx - 1].x;
-            delta_y = glyph.y - glyphs[idx - 1].y;
-        }
-        x += delta_x;
-        y += delta_y;
-
-        render_character(c, fb, x as usize, y as usize, glyph.key.px, fonts[0]);
-    }
-}
-
 pub fn check_and_handle_drag(state: &mut State) {
-    let temp_drawables = state.drawables.clone();
-    if state.left_mouse_down {
-        if !state.prev_left_mouse_down {
-            let dragged_item = temp_drawables
-                .iter()
+    if state.mouse_down(MouseButton::Left) {
+        if !state.mouse_was_down(MouseButton::Left) {
+            // topmost first
+            let dragged_item = state
+                .drawables
+                .draw_order(None)
+                .into_iter()
                 .rev()
-                .enumerate()
+                .map(|id| (id, &state.drawables[id]))
                 .find(|(_, item)| item.contains(state.mouse_coords) && item.is_draggable());
 
-            if let Some((index, item)) = dragged_item {
-                state.drag_item_id = Some((temp_drawables.len() - 1) - index);
+            if let Some((id, item)) = dragged_item {
+                state.drag_item_id = Some(id);
                 state.drag_item_initial_coords = Some(item.get_coords());
                 state.initial_mouse_down_coords = Some(state.mouse_coords);
             } else {
                 state.drag_item_id = None;
                 state.drag_item_initial_coords = None;
             }
-        } else if let (
-            Some(index),
-            Some((initial_mouse_x, initial_mouse_y)),
-            Some(initial_item_coords),
-        ) = (
-            state.drag_item_id,
-            state.initial_mouse_down_coords,
-            state.drag_item_initial_coords,
-        ) {
+        } else if let (Some(id), Some(shifted_coords)) =
+            (state.drag_item_id, dragged_coords(state))
+        {
             // drag
-            let drawable = &mut state.drawables[index];
-            let x_shift = (state.mouse_coords.0 as i32) - (initial_mouse_x as i32);
-            let y_shift = (state.mouse_coords.1 as i32) - (initial_mouse_y as i32);
-
-            let shifted_coords = coord_shift(initial_item_coords, (x_shift, y_shift));
+            let drawable = match state.drawables.get_mut(id) {
+                Some(drawable) => drawable,
+                None => {
+                    // removed out from under us, so there's nothing left to drag
+                    state.drag_item_id = None;
+                    state.drag_item_initial_coords = None;
+                    return;
+                }
+            };
             drawable.move_to(shifted_coords);
         }
-    } else if let (
-        Some(index),
-        Some((initial_mouse_x, initial_mouse_y)),
-        Some(initial_item_coords),
-    ) = (
-        state.drag_item_id,
-        state.initial_mouse_down_coords,
-        state.drag_item_initial_coords,
-    ) {
+    } else if let (Some(id), Some(shifted_coords)) = (state.drag_item_id, dragged_coords(state)) {
         // release
-        let dragged = &mut state.drawables[index];
 
         // item to snap to
-        let release_item = temp_drawables
-            .iter()
-            .rev()
-            .find(|item| item.contains(state.mouse_coords) && item.is_releasable(dragged));
-
-        if let Some(item) = release_item {
-            dragged.move_to(item.get_coords())
-        } else {
-            let x_shift = (state.mouse_coords.0 as i32) - (initial_mouse_x as i32);
-            let y_shift = (state.mouse_coords.1 as i32) - (initial_mouse_y as i32);
-
-            let shifted_coords = coord_shift(initial_item_coords, (x_shift, y_shift));
-            dragged.move_to(shifted_coords);
+        let release_coords =
+            drop_target(state, id).map(|target| state.drawables[target].get_coords());
+
+        if let Some(dragged) = state.drawables.get_mut(id) {
+            dragged.move_to(release_coords.unwrap_or(shifted_coords));
         }
 
         state.drag_item_id = None;
@@ -858,7 +855,7 @@ pub fn check_and_handle_drag(state: &mut State) {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Rect {
     pub x: usize,
     pub y: usize,
@@ -867,24 +864,51 @@ pub struct Rect {
 }
 
 // Two fields, one for whether it can snap to one like it, and whether one like it can snap to it
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum DraggableSnapType {
     Card(bool, bool),
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
-pub enum FontFamily {
-    CardBody,
-    CardTitle,
-    GameTitle
+// Which part of the frame a drawable belongs to. Layers are painted back to
+// front in this order, and within a layer by z-index, then by position in
+// state.drawables. Anything not put on a layer is on Board at z-index 0.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    Background,
+    Board,
+    Cards,
+    Ui,
+    // whatever's being dragged is drawn here, whichever layer it's on
+    Dragged,
+    Overlay,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Drawable {
     Rectangle(Rect, Color, Option<DraggableSnapType>),
     RectOutlined(Rect, Color, Option<DraggableSnapType>),
-    Text(Rect, String, FontFamily, f32),
-    PlayedCard(Rect, String, Color, Option<DraggableSnapType>),
+    Text(Rect, String, FontId, f32, TextStyle),
+    PlayedCard(Rect, String, Color, Option<DraggableSnapType>, TextStyle),
+    // the rect is where the sprite lands; it gets scaled to fill it
+    Sprite(Rect, Sprite, Option<DraggableSnapType>),
+    // children are drawn in order and can't paint outside the rect; nest these
+    // to build up a clip stack
+    Clipped(Rect, Vec<Drawable>),
+    // from, to and thickness; the ends are rounded
+    Line(FbCoords, FbCoords, usize, ShapeStyle),
+    // fills the rect, so a square one (see circle_rect) is a circle
+    Ellipse(Rect, ShapeStyle, Option<DraggableSnapType>),
+    // the points have to make a convex shape
+    Polygon(Vec<FbCoords>, ShapeStyle, Option<DraggableSnapType>),
+    // the usize is the corner radius
+    RoundedRect(Rect, usize, ShapeStyle, Option<DraggableSnapType>),
+    // a drawable on a layer at a z-index; see on_layer
+    Layered(Layer, i32, Box<Drawable>),
+}
+
+fn shift_point(point: &mut FbCoords, (x, y): (i32, i32)) {
+    point.0 = max(point.0 as i32 + x, 0) as usize;
+    point.1 = max(point.1 as i32 + y, 0) as usize;
 }
 
 impl Drawable {
@@ -892,8 +916,51 @@ impl Drawable {
         match self {
             Drawable::Rectangle(rect, _, _) => *rect,
             Drawable::RectOutlined(rect, _, _) => *rect,
-            Drawable::Text(rect, _, _, _) => *rect,
-            Drawable::PlayedCard(rect, _, _, _) => *rect,
+            Drawable::Text(rect, _, _, _, _) => *rect,
+            Drawable::PlayedCard(rect, _, _, _, _) => *rect,
+            Drawable::Sprite(rect, _, _) => *rect,
+            Drawable::Clipped(rect, _) => *rect,
+            Drawable::Line(..) | Drawable::Polygon(..) => self.shape().unwrap().0.bounds(),
+            Drawable::Ellipse(rect, _, _) => *rect,
+            Drawable::RoundedRect(rect, _, _, _) => *rect,
+            Drawable::Layered(_, _, inner) => inner.get_rect(),
+        }
+    }
+
+    // Puts the drawable on a layer at a z-index, replacing whatever it was on.
+    pub fn on_layer(self, layer: Layer, z: i32) -> Drawable {
+        match self {
+            Drawable::Layered(_, _, inner) => Drawable::Layered(layer, z, inner),
+            other => Drawable::Layered(layer, z, Box::new(other)),
+        }
+    }
+
+    pub fn layer(self: &Drawable) -> Layer {
+        match self {
+            Drawable::Layered(layer, _, _) => *layer,
+            _ => Layer::Board,
+        }
+    }
+
+    pub fn z_index(self: &Drawable) -> i32 {
+        match self {
+            Drawable::Layered(_, z, _) => *z,
+            _ => 0,
+        }
+    }
+
+    // The vector shape behind the shape drawables, and how it's painted.
+    fn shape(self: &Drawable) -> Option<(Shape, ShapeStyle)> {
+        match self {
+            Drawable::Line(from, to, thickness, style) => {
+                Some((Shape::line(*from, *to, *thickness), *style))
+            }
+            Drawable::Ellipse(rect, style, _) => Some((Shape::ellipse(*rect), *style)),
+            Drawable::Polygon(points, style, _) => Some((Shape::polygon(points), *style)),
+            Drawable::RoundedRect(rect, radius, style, _) => {
+                Some((Shape::rounded_rect(*rect, *radius), *style))
+            }
+            _ => None,
         }
     }
 
@@ -906,12 +973,24 @@ impl Drawable {
             Drawable::RectOutlined(rect, _, _) => {
                 (x >= rect.x && x <= rect.x + rect.w) && (y >= rect.y && y <= rect.y + rect.h)
             }
-            Drawable::Text(rect, _, _, _) => {
+            Drawable::Text(rect, _, _, _, _) => {
+                (x >= rect.x && x <= rect.x + rect.w) && (y >= rect.y && y <= rect.y + rect.h)
+            }
+            Drawable::PlayedCard(rect, _, _, _, _) => {
+                (x >= rect.x && x <= rect.x + rect.w) && (y >= rect.y && y <= rect.y + rect.h)
+            }
+            Drawable::Sprite(rect, _, _) => {
                 (x >= rect.x && x <= rect.x + rect.w) && (y >= rect.y && y <= rect.y + rect.h)
             }
-            Drawable::PlayedCard(rect, _, _, _) => {
+            Drawable::Clipped(rect, _) => {
                 (x >= rect.x && x <= rect.x + rect.w) && (y >= rect.y && y <= rect.y + rect.h)
             }
+            // shapes only count where they'd actually paint
+            Drawable::Line(..) | Drawable::Ellipse(..) | Drawable::Polygon(..) | Drawable::RoundedRect(..) => {
+                let (shape, style) = self.shape().unwrap();
+                shape.covers(coord, &style)
+            }
+            Drawable::Layered(_, _, inner) => inner.contains(coord),
         }
     }
 
@@ -919,17 +998,26 @@ impl Drawable {
         match self {
             Drawable::Rectangle(rect, _, _) => (rect.x, rect.y),
             Drawable::RectOutlined(rect, _, _) => (rect.x, rect.y),
-            Drawable::Text(rect, _, _, _) => (rect.x, rect.y),
-            Drawable::PlayedCard(rect, _, _, _) => (rect.x, rect.y),
+            Drawable::Text(rect, _, _, _, _) => (rect.x, rect.y),
+            Drawable::PlayedCard(rect, _, _, _, _) => (rect.x, rect.y),
+            Drawable::Sprite(rect, _, _) => (rect.x, rect.y),
+            Drawable::Clipped(rect, _) => (rect.x, rect.y),
+            Drawable::Line(..) | Drawable::Polygon(..) => {
+                let rect = self.get_rect();
+                (rect.x, rect.y)
+            }
+            Drawable::Ellipse(rect, _, _) => (rect.x, rect.y),
+            Drawable::RoundedRect(rect, _, _, _) => (rect.x, rect.y),
+            Drawable::Layered(_, _, inner) => inner.get_coords(),
         }
     }
 
     // pub fn change_text(self: &mut Drawable, new_s: String) {
     //     match self {
-    //         Drawable::Text(_, mut s, _, _) => {
+    //         Drawable::Text(_, mut s, _, _, _) => {
     //             s = new_s;
     //         }
-    //         Drawable::PlayedCard(_, mut s, _, _) => {
+    //         Drawable::PlayedCard(_, mut s, _, _, _) => {
     //             s = new_s;
     //         }
     //         Drawable::Rectangle(_, _, _) => {}
@@ -949,14 +1037,39 @@ impl Drawable {
                 rect.x = max(rect.x as i32 + x, 0) as usize;
                 rect.y = max(rect.y as i32 + y, 0) as usize;
             }
-            &mut Drawable::Text(mut rect, _, _, _) => {
+            Drawable::Text(rect, ..) => {
+                rect.x = max(rect.x as i32 + x, 0) as usize;
+                rect.y = max(rect.y as i32 + y, 0) as usize;
+            }
+            Drawable::PlayedCard(rect, _, _, _, _) => {
+                rect.x = max(rect.x as i32 + x, 0) as usize;
+                rect.y = max(rect.y as i32 + y, 0) as usize;
+            }
+            Drawable::Sprite(rect, _, _) => {
                 rect.x = max(rect.x as i32 + x, 0) as usize;
                 rect.y = max(rect.y as i32 + y, 0) as usize;
             }
-            Drawable::PlayedCard(rect, _, _, _) => {
+            Drawable::Clipped(rect, children) => {
                 rect.x = max(rect.x as i32 + x, 0) as usize;
                 rect.y = max(rect.y as i32 + y, 0) as usize;
+                children.iter_mut().for_each(|child| child.shift(amount));
             }
+            Drawable::Line(from, to, _, _) => {
+                shift_point(from, amount);
+                shift_point(to, amount);
+            }
+            Drawable::Ellipse(rect, _, _) => {
+                rect.x = max(rect.x as i32 + x, 0) as usize;
+                rect.y = max(rect.y as i32 + y, 0) as usize;
+            }
+            Drawable::Polygon(points, _, _) => {
+                points.iter_mut().for_each(|point| shift_point(point, amount));
+            }
+            Drawable::RoundedRect(rect, _, _, _) => {
+                rect.x = max(rect.x as i32 + x, 0) as usize;
+                rect.y = max(rect.y as i32 + y, 0) as usize;
+            }
+            Drawable::Layered(_, _, inner) => inner.shift(amount),
         }
     }
 
@@ -971,14 +1084,39 @@ impl Drawable {
                 rect.x = x;
                 rect.y = y;
             }
-            Drawable::Text(rect, _, _, _) => {
+            Drawable::Text(rect, _, _, _, _) => {
+                rect.x = x;
+                rect.y = y;
+            }
+            Drawable::PlayedCard(rect, _, _, _, _) => {
+                rect.x = x;
+                rect.y = y;
+            }
+            Drawable::Sprite(rect, _, _) => {
+                rect.x = x;
+                rect.y = y;
+            }
+            Drawable::Clipped(rect, children) => {
+                // children keep their position relative to the clip
+                let delta = (x as i32 - rect.x as i32, y as i32 - rect.y as i32);
+                children.iter_mut().for_each(|child| child.shift(delta));
+                rect.x = x;
+                rect.y = y;
+            }
+            // these move by their top-left corner, like everything else
+            Drawable::Line(..) | Drawable::Polygon(..) => {
+                let (old_x, old_y) = self.get_coords();
+                self.shift((x as i32 - old_x as i32, y as i32 - old_y as i32));
+            }
+            Drawable::Ellipse(rect, _, _) => {
                 rect.x = x;
                 rect.y = y;
             }
-            Drawable::PlayedCard(rect, _, _, _) => {
+            Drawable::RoundedRect(rect, _, _, _) => {
                 rect.x = x;
                 rect.y = y;
             }
+            Drawable::Layered(_, _, inner) => inner.move_to(coords),
         }
     }
 
@@ -986,8 +1124,15 @@ impl Drawable {
         match self {
             Drawable::Rectangle(_, _, drag_type) => *drag_type,
             Drawable::RectOutlined(_, _, drag_type) => *drag_type,
-            Drawable::Text(_, _,_, _) => None,
-            Drawable::PlayedCard(_, _, _, drag_type) => *drag_type,
+            Drawable::Text(_, _, _, _, _) => None,
+            Drawable::PlayedCard(_, _, _, drag_type, _) => *drag_type,
+            Drawable::Sprite(_, _, drag_type) => *drag_type,
+            Drawable::Clipped(_, _) => None,
+            Drawable::Line(_, _, _, _) => None,
+            Drawable::Ellipse(_, _, drag_type) => *drag_type,
+            Drawable::Polygon(_, _, drag_type) => *drag_type,
+            Drawable::RoundedRect(_, _, _, drag_type) => *drag_type,
+            Drawable::Layered(_, _, inner) => inner.get_drag_type(),
         }
     }
 
@@ -1018,110 +1163,142 @@ impl Rect {
     pub fn new(x: usize, y: usize, w: usize, h: usize) -> Rect {
         Rect { x, y, w, h }
     }
-}
 
-fn draw_objects(state: &mut State, drawables: Vec<Drawable>) {
-    drawables.into_iter().enumerate().for_each(|(_, obj)| {
-        match obj {
-            Drawable::Rectangle(r, c, _) => {
-                // println!("rectangle x: {:?}", r.x);
-                rectangle(&mut state.fb2d, r, c);
-            }
-            Drawable::RectOutlined(r, c, _) => {
-                rect_outlined(&mut state.fb2d, r, c);
-            }
-            Drawable::Text(r, s, family, size) => {
-                match family {
-                    FontFamily::CardBody => draw_layout_text(&mut state.fb2d, s, r, &state.card_body_font, size),
-                    FontFamily::CardTitle => draw_layout_text(&mut state.fb2d, s, r, &state.game_title_font, size),
-                    FontFamily::GameTitle => draw_layout_text(&mut state.fb2d, s, r, &state.game_title_font, size),
-                }
-            }
-            Drawable::PlayedCard(r, s, c, _) => {
-                rectangle(&mut state.fb2d, r, c);
-                draw_layout_text(&mut state.fb2d, s, r, &state.card_body_font, 10.0); //size doesn't matter anyway.
-            }
-        }
-    });
-}
+    pub fn right(&self) -> usize {
+        self.x.saturating_add(self.w)
+    }
 
-// Here's what clear looks like, though we won't use it
-#[allow(dead_code)]
-pub fn clear(fb: &mut [Color], c: Color) {
-    fb.fill(c);
-}
+    pub fn bottom(&self) -> usize {
+        self.y.saturating_add(self.h)
+    }
 
-#[allow(dead_code)]
-fn line(fb: &mut [Color], x0: usize, x1: usize, y: usize, c: Color) {
-    let min_x = min(max(0, x0), WIDTH);
-    let max_x = max(min(WIDTH, x1), 0);
-    if y >= HEIGHT {
-        return;
+    pub fn is_empty(&self) -> bool {
+        self.w == 0 || self.h == 0
+    }
+
+    pub fn contains_point(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
     }
-    fb[y * WIDTH + min_x..(y * WIDTH + max_x)].fill(c);
-}
 
-#[allow(dead_code)]
-fn rectangle(fb: &mut [Color], r: Rect, c: Color) {
-    // assert!(r.w + r.x < WIDTH);
-    // assert!(r.h + r.y < HEIGHT);
+    // The smallest rect covering both. Empty rects don't count.
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let x = min(self.x, other.x);
+        let y = min(self.y, other.y);
+        Rect {
+            x,
+            y,
+            w: max(self.right(), other.right()) - x,
+            h: max(self.bottom(), other.bottom()) - y,
+        }
+    }
 
-    for i in (r.y)..(r.y + r.h) {
-        line(fb, r.x, r.x + r.w, i, c);
+    // The overlap of two rects; zero-sized if they don't touch.
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = max(self.x, other.x);
+        let y = max(self.y, other.y);
+        let right = min(self.right(), other.right());
+        let bottom = min(self.bottom(), other.bottom());
+        Rect {
+            x,
+            y,
+            w: right.saturating_sub(x),
+            h: bottom.saturating_sub(y),
+        }
     }
 }
 
-#[allow(dead_code)]
-fn rect_outlined(fb: &mut [Color], r: Rect, c: Color) {
-    let t = 1;
-    let x = r.x;
-    let y = r.y;
-    let h = r.h;
-    let w = r.w;
-    (y..(y + (t))).for_each(|y1| line(fb, x, x + w, y1, c));
-    ((y + h - t)..(y + h)).for_each(|y1| line(fb, x, x + w, y1, c));
-
-    ((y + t)..(y + h - (t))).for_each(|y1| {
-        line(fb, x, x + t, y1, c);
-        line(fb, x + w - t, x + w, y1, c);
-    });
+// The indices of drawables in the order they're painted, back to front: by
+// layer, then z-index, and ties keep their order in the list. lifted (the item
+// being dragged) goes on Layer::Dragged. Hit testing walks this backwards, so
+// whatever is drawn on top is what gets clicked.
+pub fn draw_order(drawables: &[Drawable], lifted: Option<usize>) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..drawables.len()).collect();
+    // sort_by_key is stable
+    order.sort_by_key(|&i| paint_key(&drawables[i], lifted == Some(i)));
+    order
 }
 
-#[allow(dead_code)]
-fn point(fb: &mut [Color], x: usize, y: usize, c: Color) {
-    assert!(y < HEIGHT);
-    assert!(x < WIDTH);
-    fb[y * WIDTH + x] = c;
+pub(crate) fn paint_key(drawable: &Drawable, lifted: bool) -> (Layer, i32) {
+    let layer = if lifted { Layer::Dragged } else { drawable.layer() };
+    (layer, drawable.z_index())
 }
 
-#[allow(dead_code)]
-fn line_bresenham(
-    fb: &mut [Color],
-    (x0, y0): (usize, usize),
-    (x1, y1): (usize, usize),
-    col: Color,
-) {
-    let mut x = x0 as i64;
-    let mut y = y0 as i64;
-    let x0 = x0 as i64;
-    let y0 = y0 as i64;
-    let x1 = x1 as i64;
-    let y1 = y1 as i64;
-    let dx = (x1 - x0).abs();
-    let sx: i64 = if x0 < x1 { 1 } else { -1 };
-    let dy = -(y1 - y0).abs();
-    let sy: i64 = if y0 < y1 { 1 } else { -1 };
-    let mut err = dx + dy;
-    while x != x1 || y != y1 {
-        fb[(y as usize * WIDTH + x as usize)..(y as usize * WIDTH + (x as usize + 1))].fill(col);
-        let e2 = 2 * err;
-        if dy <= e2 {
-            err += dy;
-            x += sx;
+// Repaints the dirty regions of fb2d from the retained scene. Each region is
+// cleared to the background and everything overlapping it is redrawn, clipped
+// to the region, in the usual back-to-front order.
+fn draw_objects(state: &mut State, dirty: &[Rect]) {
+    let mut canvas = Canvas::new(&mut state.fb2d, state.config.width, state.config.height);
+    for region in dirty {
+        canvas.push_clip(*region);
+        fill_rect(&mut canvas, *region, state.bg_color);
+        let items = state.scene.items().iter().zip(state.scene.bounds());
+        for (obj, bounds) in items {
+            if !bounds.intersect(region).is_empty() {
+                draw_object(&mut canvas, &mut state.glyph_cache, &state.fonts, obj);
+            }
+        }
+        canvas.pop_clip();
+    }
+}
+
+// Where a drawable paints. Text can spill outside its rect, so it's measured.
+fn drawable_bounds(cache: &mut GlyphCache, fonts: &FontRegistry, obj: &Drawable) -> Rect {
+    match obj {
+        Drawable::Text(r, s, family, size, style) => layout_text_bounds(cache, s, *r, fonts, *family, *size, style),
+        Drawable::Layered(_, _, inner) => drawable_bounds(cache, fonts, inner),
+        _ => obj.get_rect(),
+    }
+}
+
+fn draw_object(canvas: &mut Canvas, cache: &mut GlyphCache, fonts: &FontRegistry, obj: &Drawable) {
+    match *obj {
+        Drawable::Rectangle(r, c, _) => {
+            // println!("rectangle x: {:?}", r.x);
+            rectangle(canvas, r, c);
+        }
+        Drawable::RectOutlined(r, c, _) => {
+            rect_outlined(canvas, r, c);
+        }
+        Drawable::Text(r, ref s, family, size, ref style) => {
+            draw_layout_text(canvas, cache, s, r, fonts, family, size, style);
+        }
+        Drawable::PlayedCard(r, ref s, c, _, ref style) => {
+            rectangle(canvas, r, c);
+            // card text never spills out of the card
+            canvas.with_clip(r, |canvas| {
+                draw_layout_text(canvas, cache, s, r, fonts, FontId::CARD_BODY, CARD_TEXT_SIZE, style);
+            });
+        }
+        Drawable::Sprite(r, ref sprite, _) => {
+            draw_sprite(canvas, sprite, r);
+        }
+        Drawable::Clipped(r, ref children) => {
+            canvas.push_clip(r);
+            for i in draw_order(children, None) {
+                draw_object(canvas, cache, fonts, &children[i]);
+            }
+            canvas.pop_clip();
+        }
+        Drawable::Line(from, to, thickness, ref style) => {
+            draw_line(canvas, from, to, thickness, style);
+        }
+        Drawable::Ellipse(r, ref style, _) => {
+            draw_ellipse(canvas, r, style);
+        }
+        Drawable::Polygon(ref points, ref style, _) => {
+            draw_polygon(canvas, points, style);
+        }
+        Drawable::RoundedRect(r, radius, ref style, _) => {
+            draw_rounded_rect(canvas, r, radius, style);
         }
-        if e2 <= dx {
-            err += dx;
-            y += sy;
+        Drawable::Layered(_, _, ref inner) => {
+            draw_object(canvas, cache, fonts, inner);
         }
     }
 }
@@ -1139,33 +1316,6 @@ pub fn handle_mana(curr_manas: (usize, usize), delta: usize, turn: usize) -> (us
     (p1_mana, p2_mana)
 }
 
-fn window_size_dependent_setup(
-    images: &[Arc<SwapchainImage<Window>>],
-    render_pass: Arc<RenderPass>,
-    viewport: &mut Viewport,
-) -> (Vec<Arc<Framebuffer>>, (f64, f64)) {
-    let dimensions = images[0].dimensions().width_height();
-    viewport.dimensions = [dimensions[0] as f32, dimensions[1] as f32];
-    let mut window_width = dimensions[0].into();
-    let mut window_height = dimensions[1].into();
-
-    println! {"window width: {:?}", window_width};
-    (
-        images
-            .iter()
-            .map(|image| {
-                let view = ImageView::new(image.clone()).unwrap();
-                Framebuffer::start(render_pass.clone())
-                    .add(view)
-                    .unwrap()
-                    .build()
-                    .unwrap()
-            })
-            .collect::<Vec<_>>(),
-        (window_width, window_height),
-    )
-}
-
 pub fn move_unit(pos: Rect, speed: usize) -> Rect {
     let new_pos = Rect {
         x: pos.x + speed,
@@ -1186,379 +1336,124 @@ pub fn move_back(pos: Rect, speed: usize) -> Rect {
     return new_pos;
 }
 
-pub fn setup() -> State {
-    let required_extensions = vulkano_win::required_extensions();
-    let instance = Instance::new(None, Version::V1_1, &required_extensions, None).unwrap();
-    let event_loop = EventLoop::new();
-    let surface = WindowBuilder::new()
-        .build_vk_surface(&event_loop, instance.clone())
-        .unwrap();
-    let device_extensions = DeviceExtensions {
-        khr_swapchain: true,
-        ..DeviceExtensions::none()
-    };
-    let (physical_device, queue_family) = PhysicalDevice::enumerate(&instance)
-        .filter(|&p| p.supported_extensions().is_superset_of(&device_extensions))
-        .filter_map(|p| {
-            p.queue_families()
-                .find(|&q| q.supports_graphics() && surface.is_supported(q).unwrap_or(false))
-                .map(|q| (p, q))
-        })
-        .min_by_key(|(p, _)| match p.properties().device_type {
-            PhysicalDeviceType::DiscreteGpu => 0,
-            PhysicalDeviceType::IntegratedGpu => 1,
-            PhysicalDeviceType::VirtualGpu => 2,
-            PhysicalDeviceType::Cpu => 3,
-            PhysicalDeviceType::Other => 4,
-        })
-        .unwrap();
-    let (device, mut queues) = Device::new(
-        physical_device,
-        &Features::none(),
-        &physical_device
-            .required_extensions()
-            .union(&device_extensions),
-        [(queue_family, 0.5)].iter().cloned(),
-    )
-    .unwrap();
-    let queue = queues.next().unwrap();
-    let (swapchain, images) = {
-        let caps = surface.capabilities(physical_device).unwrap();
-        let composite_alpha = caps.supported_composite_alpha.iter().next().unwrap();
-        let format = caps.supported_formats[0].0;
-        let dimensions: [u32; 2] = surface.window().inner_size().into();
-        Swapchain::start(device.clone(), surface.clone())
-            .num_images(caps.min_image_count)
-            .format(format)
-            .dimensions(dimensions)
-            .usage(ImageUsage::color_attachment())
-            .sharing_mode(&queue)
-            .composite_alpha(composite_alpha)
-            .build()
-            .unwrap()
-    };
-
-    // load in the font used for text rendering
-    let font_settings: fontdue::FontSettings = fontdue::FontSettings::default();
-
-    let card_body_font: fontdue::Font = fontdue::Font::from_bytes(FONT_DATA_ROBOTO, font_settings).unwrap();
-    let game_title_font: fontdue::Font = fontdue::Font::from_bytes(FONT_DATA_CARTER, font_settings).unwrap();
-    // We now create a buffer that will store the shape of our triangl
-
-    let vertex_buffer = CpuAccessibleBuffer::from_iter(
-        device.clone(),
-        BufferUsage::all(),
-        false,
-        [
-            Vertex {
-                position: [-1.0, -1.0],
-                uv: [0.0, 0.0],
-            },
-            Vertex {
-                position: [3.0, -1.0],
-                uv: [2.0, 0.0],
-            },
-            Vertex {
-                position: [-1.0, 3.0],
-                uv: [0.0, 2.0],
-            },
-        ]
-        .iter()
-        .cloned(),
-    )
-    .unwrap();
-    mod vs {
-        vulkano_shaders::shader! {
-            ty: "vertex",
-            src: "
-                #version 450
-
-                layout(location = 0) in vec2 position;
-                layout(location = 1) in vec2 uv;
-                layout(location = 0) out vec2 out_uv;
-                void main() {
-                    gl_Position = vec4(position, 0.0, 1.0);
-                    out_uv = uv;
-                }
-            "
-        }
+// An arrow from one point to another, e.g. for an attack between battle
+// slots: a line for the shaft and a triangle for the head, whose tip is at to.
+pub fn arrow(from: FbCoords, to: FbCoords, thickness: usize, style: ShapeStyle) -> Vec<Drawable> {
+    let (fx, fy) = (from.0 as f32, from.1 as f32);
+    let (tx, ty) = (to.0 as f32, to.1 as f32);
+    let len = ((tx - fx).powi(2) + (ty - fy).powi(2)).sqrt();
+    if len == 0.0 {
+        return vec![];
     }
+    let (dx, dy) = ((tx - fx) / len, (ty - fy) / len);
+    let head = (thickness as f32 * 4.0).max(10.0).min(len);
+    // where the head meets the shaft, and how far the head sticks out each side
+    let (bx, by) = (tx - dx * head, ty - dy * head);
+    let half = head * 0.6;
+    let point = |x: f32, y: f32| (x.round().max(0.0) as usize, y.round().max(0.0) as usize);
+    vec![
+        Drawable::Line(from, point(bx, by), thickness, style),
+        Drawable::Polygon(
+            vec![to, point(bx - dy * half, by + dx * half), point(bx + dy * half, by - dx * half)],
+            style,
+            None,
+        ),
+    ]
+}
 
-    mod fs {
-        vulkano_shaders::shader! {
-            ty: "fragment",
-            src: "
-                #version 450
+// Opens a window and renders through Vulkan at the default resolution.
+pub fn setup() -> State {
+    setup_with(EngineConfig::default())
+}
 
-                layout(set = 0, binding = 0) uniform sampler2D tex;
-                layout(location = 0) in vec2 uv;
-                layout(location = 0) out vec4 f_color;
+pub fn setup_with(config: EngineConfig) -> State {
+    let event_loop = EventLoop::new();
+    let renderer = VulkanRenderer::new(&event_loop, config.width, config.height);
+    new_state(config, Box::new(renderer), Some(event_loop))
+}
 
-                void main() {
-                    f_color = texture(tex, uv);
-                }
-            "
-        }
-    }
+// Same engine state with no window, no event loop and no GPU. draw() still
+// rasterizes everything into fb2d, so frames can be run and inspected on CI.
+pub fn setup_headless() -> State {
+    setup_headless_with(EngineConfig::default())
+}
 
-    let vs = vs::load(device.clone()).unwrap();
-    let fs = fs::load(device.clone()).unwrap();
+pub fn setup_headless_with(config: EngineConfig) -> State {
+    let renderer = HeadlessRenderer::new(config.width, config.height);
+    new_state(config, Box::new(renderer), None)
+}
 
+fn new_state(
+    config: EngineConfig,
+    renderer: Box<dyn Renderer>,
+    event_loop: Option<EventLoop<()>>,
+) -> State {
     // Here's our (2D drawing) framebuffer.
-    // let fb2d_l = [(128 as u8, 64 as u8, 64 as u8, 255 as u8); WIDTH * HEIGHT];
-    // let mut fb2d = vec![fb2d_l];
-    let fb2d = vec![(128 as u8, 64 as u8, 64 as u8, 255 as u8); WIDTH * HEIGHT];
-    // We'll work on it locally, and copy it to a GPU buffer every frame.
-    // Then on the GPU, we'll copy it into an Image.
-    let fb2d_buffer = CpuAccessibleBuffer::from_iter(
-        device.clone(),
-        BufferUsage::transfer_source(),
-        false,
-        (0..WIDTH * HEIGHT).map(|_| (255_u8, 0_u8, 0_u8, 0_u8)),
-    )
-    .unwrap();
-    // Let's set up the Image we'll copy into:
-    let dimensions = ImageDimensions::Dim2d {
-        width: WIDTH as u32,
-        height: HEIGHT as u32,
-        array_layers: 1,
-    };
-    //image n GPU
-    let fb2d_image = StorageImage::with_usage(
-        device.clone(),
-        dimensions,
-        Format::R8G8B8A8_UNORM,
-        ImageUsage {
-            // This part is key!
-            transfer_destination: true,
-            sampled: true,
-            storage: true,
-            transfer_source: false,
-            color_attachment: false,
-            depth_stencil_attachment: false,
-            transient_attachment: false,
-            input_attachment: false,
-        },
-        ImageCreateFlags::default(),
-        std::iter::once(queue_family),
-    )
-    .unwrap();
-    // Get a view on it to use as a texture:
-    let fb2d_texture = ImageView::new(fb2d_image.clone()).unwrap();
-
-    let fb2d_sampler = Sampler::new(
-        device.clone(),
-        Filter::Linear,
-        Filter::Linear,
-        MipmapMode::Nearest,
-        SamplerAddressMode::Repeat,
-        SamplerAddressMode::Repeat,
-        SamplerAddressMode::Repeat,
-        0.0,
-        1.0,
-        0.0,
-        0.0,
-    )
-    .unwrap();
-
-    let render_pass = vulkano::single_pass_renderpass!(
-        device.clone(),
-        attachments: {
-            color: {
-                // Pro move: We're going to cover the screen completely. Trust us!
-                load: DontCare,
-                store: Store,
-                format: swapchain.format(),
-                samples: 1,
-            }
-        },
-        pass: {
-            color: [color],
-            depth_stencil: {}
-        }
-    )
-    .unwrap();
-    let pipeline = GraphicsPipeline::start()
-        .vertex_input_state(BuffersDefinition::new().vertex::<Vertex>())
-        .vertex_shader(vs.entry_point("main").unwrap(), ())
-        .input_assembly_state(InputAssemblyState::new())
-        .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
-        .fragment_shader(fs.entry_point("main").unwrap(), ())
-        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
-        .build(device.clone())
-        .unwrap();
-
-    let layout = pipeline.layout().descriptor_set_layouts().get(0).unwrap();
-    let mut set_builder = PersistentDescriptorSet::start(layout.clone());
-
-    set_builder
-        .add_sampled_image(fb2d_texture, fb2d_sampler)
-        .unwrap();
-
-    let set = set_builder.build().unwrap();
-
-    let mut viewport = Viewport {
-        origin: [0.0, 0.0],
-        dimensions: [0.0, 0.0],
-        depth_range: 0.0..1.0,
-    };
+    // We'll work on it locally, and the renderer copies it to the GPU every frame.
+    let fb2d = vec![(128 as u8, 64 as u8, 64 as u8, 255 as u8); config.width * config.height];
 
-    let (framebuffers, (window_width, window_height)) =
-        window_size_dependent_setup(&images, render_pass.clone(), &mut viewport);
-    let recreate_swapchain = false;
-    let previous_frame_end = Some(sync::now(device.clone()).boxed());
+    let (window_width, window_height) = renderer.window_size();
+    let seed = rand::thread_rng().gen();
 
     State {
+        config,
         fb2d,
-        drawables: vec![],
+        drawables: Drawables::new(),
         p1_units: vec![],
         p2_units: vec![],
         bg_color: (255, 255, 255, 255),
-        previous_frame_end,
-        recreate_swapchain,
         event_loop,
-        fb2d_buffer,
+        renderer,
         now_keys: [false; 255],
         prev_keys: [false; 255],
-        fb2d_image,
-        queue,
-        swapchain,
-        viewport,
-        framebuffers,
-        pipeline,
-        render_pass,
-        dimensions,
-        vs,
-        fs,
-        surface,
-        device,
-        set,
-        vertex_buffer,
+        input_map: InputMap::new(),
         window_width,
         window_height,
-        left_mouse_down: false,
-        prev_left_mouse_down: false,
-        mouse_coords: (WIDTH, HEIGHT),
-        prev_mouse_coords: (WIDTH, HEIGHT),
+        now_mouse_buttons: [false; MOUSE_BUTTONS],
+        prev_mouse_buttons: [false; MOUSE_BUTTONS],
+        wheel_delta: (0.0, 0.0),
+        mouse_coords: (config.width, config.height),
+        prev_mouse_coords: (config.width, config.height),
+        hovered: None,
+        hover_events: vec![],
         initial_mouse_down_coords: None,
         drag_item_id: None,
         drag_item_initial_coords: None,
-        card_body_font,
-        game_title_font,
+        // the built-in fonts; games can load their own into this
+        fonts: FontRegistry::new(),
+        glyph_cache: GlyphCache::new(),
+        scene: Scene::new(config.width, config.height),
+        screenshot_key: Some(VirtualKeyCode::F12),
+        screenshot_dir: std::path::PathBuf::from("screenshots"),
+        seed,
+        rng: StdRng::seed_from_u64(seed),
+        frame: 0,
+        frame_time: std::time::Duration::ZERO,
+        clock: std::time::Duration::ZERO,
+        started_at: std::time::Instant::now(),
+        last_frame_at: None,
+        recorder: None,
+        replay: None,
     }
 }
 
 pub fn draw(state: &mut State) {
-    //instead, take a state struct
-    {
-        // We need to synchronize here to send new data to the GPU.
-        // We can't send the new framebuffer until the previous frame is done being drawn.
-        // Dropping the future will block until it's done.
-        if let Some(mut fut) = state.previous_frame_end.take() {
-            fut.cleanup_finished();
-        }
-    }
-
-    // First clear the framebuffer...
-    clear(&mut state.fb2d, state.bg_color);
-    // clear(&mut state.fb2d.as_slice()[0], state.bg_color);
+    // First work out what changed since the last frame...
+    state.scene.check_fonts(state.fonts.generation());
+    let order = state.drawables.draw_order(state.drag_item_id);
+    let sorted = order.iter().map(|&id| &state.drawables[id]);
+    let dirty = state.scene.update(sorted, state.bg_color, |obj| {
+        drawable_bounds(&mut state.glyph_cache, &state.fonts, obj)
+    });
 
     // here is where we draw!!!
-    draw_objects(state, state.drawables.clone());
-
-    // Now we can copy into our buffer.
-    {
-        let writable_fb = &mut *state.fb2d_buffer.write().unwrap();
-        writable_fb.copy_from_slice(&state.fb2d); //copy frame buffer into GPU
-    }
-
-    if state.recreate_swapchain {
-        let dimensions: [u32; 2] = state.surface.window().inner_size().into();
-        let (new_swapchain, new_images) =
-            match state.swapchain.recreate().dimensions(dimensions).build() {
-                Ok(r) => r,
-                Err(SwapchainCreationError::UnsupportedDimensions) => return,
-                Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
-            };
-
-        state.swapchain = new_swapchain;
-        let setup_result = window_size_dependent_setup(
-            &new_images,
-            state.render_pass.clone(),
-            &mut state.viewport,
-        );
-
-        state.framebuffers = setup_result.0;
-        state.window_width = setup_result.1 .0;
-        state.window_height = setup_result.1 .1;
-        state.recreate_swapchain = false;
-    }
-    let (image_num, suboptimal, acquire_future) =
-        match swapchain::acquire_next_image(state.swapchain.clone(), None) {
-            Ok(r) => r,
-            Err(AcquireError::OutOfDate) => {
-                state.recreate_swapchain = true;
-                return;
-            }
-            Err(e) => panic!("Failed to acquire next image: {:?}", e),
-        };
-    if suboptimal {
-        state.recreate_swapchain = true;
-    }
+    draw_objects(state, &dirty);
 
-    let mut builder = AutoCommandBufferBuilder::primary(
-        state.device.clone(),
-        state.queue.family(),
-        CommandBufferUsage::OneTimeSubmit,
-    )
-    .unwrap();
-
-    builder
-        // Now copy that framebuffer buffer into the framebuffer image
-        .copy_buffer_to_image(state.fb2d_buffer.clone(), state.fb2d_image.clone())
-        .unwrap()
-        // And resume our regularly scheduled programming
-        .begin_render_pass(
-            state.framebuffers[image_num].clone(),
-            SubpassContents::Inline,
-            std::iter::once(vulkano::format::ClearValue::None),
-        )
-        .unwrap()
-        .set_viewport(0, [state.viewport.clone()])
-        .bind_pipeline_graphics(state.pipeline.clone())
-        .bind_descriptor_sets(
-            PipelineBindPoint::Graphics,
-            state.pipeline.layout().clone(),
-            0,
-            state.set.clone(),
-        )
-        .bind_vertex_buffers(0, state.vertex_buffer.clone())
-        .draw(state.vertex_buffer.len() as u32, 1, 0, 0)
-        .unwrap()
-        .end_render_pass()
-        .unwrap();
-
-    let command_buffer = builder.build().unwrap();
-
-    let future = acquire_future
-        .then_execute(state.queue.clone(), command_buffer)
-        .unwrap()
-        .then_swapchain_present(state.queue.clone(), state.swapchain.clone(), image_num)
-        .then_signal_fence_and_flush();
-
-    match future {
-        Ok(future) => {
-            state.previous_frame_end = Some(future.boxed());
-        }
-        Err(FlushError::OutOfDate) => {
-            state.recreate_swapchain = true;
-            state.previous_frame_end = Some(sync::now(state.device.clone()).boxed());
-        }
-        Err(e) => {
-            println!("Failed to flush future: {:?}", e);
-            state.previous_frame_end = Some(sync::now(state.device.clone()).boxed());
-        }
-    }
+    // ...then hand it to whichever backend we're running on.
+    state.renderer.present(&state.fb2d, &dirty);
+    let (window_width, window_height) = state.renderer.window_size();
+    state.window_width = window_width;
+    state.window_height = window_height;
 }
 
 pub fn handle_winit_event(
@@ -1577,15 +1472,21 @@ pub fn handle_winit_event(
             event: WindowEvent::Resized(_),
             ..
         } => {
-            state.recreate_swapchain = true;
+            state.renderer.resized();
         }
         // NewEvents: Let's start processing events.
         Event::NewEvents(_) => {
-            // Leave now_keys alone, but copy over all changed keys
-            state.prev_keys.copy_from_slice(&state.now_keys);
-            state.prev_left_mouse_down = state.left_mouse_down;
-            state.prev_mouse_coords = state.mouse_coords;
+            begin_frame(state);
         }
+        // While replaying, input comes from the recording and live input is dropped
+        Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput { .. }
+                | WindowEvent::CursorMoved { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::MouseWheel { .. },
+            ..
+        } if state.is_replaying() => {}
         // WindowEvent->KeyboardInput: Keyboard input!
         Event::WindowEvent {
             // Note this deeply nested pattern match
@@ -1605,6 +1506,13 @@ pub fn handle_winit_event(
             // It also binds these handy variable names!
             match key_state {
                 winit::event::ElementState::Pressed => {
+                    // Key repeat sends Pressed again while held, so only act on the first one
+                    if state.screenshot_key == Some(keycode) && !state.now_keys[keycode as usize] {
+                        match state.save_timestamped_screenshot() {
+                            Ok(path) => println!("saved screenshot to {}", path.display()),
+                            Err(e) => println!("Failed to save screenshot: {:?}", e),
+                        }
+                    }
                     // VirtualKeycode is an enum with a defined representation
                     state.now_keys[keycode as usize] = true;
                 }
@@ -1625,9 +1533,11 @@ pub fn handle_winit_event(
             let cursor_x = position.x / state.window_width;
             let cursor_y = position.y / state.window_height;
             state.mouse_coords = (
-                (cursor_x * WIDTH as f64) as usize,
-                (cursor_y * HEIGHT as f64) as usize,
+                (cursor_x * state.config.width as f64) as usize,
+                (cursor_y * state.config.height as f64) as usize,
             );
+            let events = update_hover(state);
+            state.hover_events.extend(events);
         }
         Event::WindowEvent {
             event:
@@ -1639,9 +1549,13 @@ pub fn handle_winit_event(
                 },
             window_id: _,
         } => {
-            if button == winit::event::MouseButton::Left {
-                state.left_mouse_down = button_state == winit::event::ElementState::Pressed;
-            }
+            state.set_mouse_button(button, button_state == winit::event::ElementState::Pressed);
+        }
+        Event::WindowEvent {
+            event: WindowEvent::MouseWheel { delta, .. },
+            ..
+        } => {
+            state.scroll(delta);
         }
         _ => {}
     }
@@ -0,0 +1,143 @@
+// A small markup language for card text:
+//
+//   [b]bold[/b]
+//   [color=#ff4040]red[/color]      (#rrggbb, #rrggbbaa or a name like red/gold)
+//   [size=18]bigger[/size]
+//   [hp] [def] [atk] [mana]         inline stat icons
+//   [[                              a literal '['
+//
+// Tags nest, unclosed tags run to the end, and anything that isn't a known tag
+// is left in the text as-is, so card JSON without markup renders unchanged.
+
+use crate::text::{Icon, TextSpan};
+use crate::Color;
+
+// The character laid out in place of an icon. It's never drawn, it just
+// reserves a roughly capital-letter-sized box that won't get wrapped apart.
+pub const ICON_PLACEHOLDER: char = 'M';
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Markup {
+    pub text: String,
+    pub spans: Vec<TextSpan>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Tag {
+    Bold,
+    Color,
+    Size,
+}
+
+pub fn parse_markup(src: &str) -> Markup {
+    let mut text = String::with_capacity(src.len());
+    let mut spans: Vec<TextSpan> = vec![];
+    // open tags and the index of the span they started
+    let mut open: Vec<(Tag, usize)> = vec![];
+
+    let mut rest = src;
+    while let Some(i) = rest.find('[') {
+        text.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        if let Some(after) = rest.strip_prefix("[[") {
+            text.push('[');
+            rest = after;
+            continue;
+        }
+        let end = match rest.find(']') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[1..end];
+        let start = text.len();
+
+        let handled = if let Some(name) = tag.strip_prefix('/') {
+            let kind = match name {
+                "b" => Some(Tag::Bold),
+                "color" => Some(Tag::Color),
+                "size" => Some(Tag::Size),
+                _ => None,
+            };
+            match kind.and_then(|kind| open.iter().rposition(|(t, _)| *t == kind)) {
+                Some(pos) => {
+                    let (_, span) = open.remove(pos);
+                    spans[span].range.end = start;
+                    true
+                }
+                None => false,
+            }
+        } else if let Some(icon) = Icon::from_name(tag) {
+            text.push(ICON_PLACEHOLDER);
+            spans.push(TextSpan {
+                icon: Some(icon),
+                ..TextSpan::new(start..text.len())
+            });
+            true
+        } else {
+            let span = TextSpan::new(start..start);
+            let opened = match tag.split_once('=') {
+                None if tag == "b" => Some((Tag::Bold, TextSpan { bold: true, ..span })),
+                Some(("color", value)) => {
+                    parse_color(value).map(|c| (Tag::Color, TextSpan { color: Some(c), ..span }))
+                }
+                Some(("size", value)) => match value.trim().parse::<f32>() {
+                    Ok(size) if size > 0.0 => Some((Tag::Size, TextSpan { size: Some(size), ..span })),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match opened {
+                Some((kind, span)) => {
+                    open.push((kind, spans.len()));
+                    spans.push(span);
+                    true
+                }
+                None => false,
+            }
+        };
+
+        if handled {
+            rest = &rest[end + 1..];
+        } else {
+            // not ours, keep the bracket as text
+            text.push('[');
+            rest = &rest[1..];
+        }
+    }
+    text.push_str(rest);
+
+    for (_, span) in open {
+        spans[span].range.end = text.len();
+    }
+    spans.retain(|span| !span.range.is_empty());
+    Markup { text, spans }
+}
+
+// Makes arbitrary text (card names from JSON, player input) safe to drop into
+// markup.
+pub fn escape_markup(s: &str) -> String {
+    s.replace('[', "[[")
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        let byte = |i: usize| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok());
+        return match hex.len() {
+            6 => Some((byte(0)?, byte(2)?, byte(4)?, 255)),
+            8 => Some((byte(0)?, byte(2)?, byte(4)?, byte(6)?)),
+            _ => None,
+        };
+    }
+    match value {
+        "white" => Some((255, 255, 255, 255)),
+        "black" => Some((0, 0, 0, 255)),
+        "grey" | "gray" => Some((160, 160, 160, 255)),
+        "red" => Some((255, 80, 80, 255)),
+        "green" => Some((90, 220, 90, 255)),
+        "blue" => Some((90, 150, 255, 255)),
+        "gold" => Some((255, 215, 0, 255)),
+        _ => None,
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{DrawableId, State};
+use winit::event::{MouseButton, MouseScrollDelta};
+
+// Room for left, right, middle and 29 extra buttons (winit's Other(0..29)).
+// Buttons past that are ignored.
+pub const MOUSE_BUTTONS: usize = 32;
+
+// Touchpads scroll in pixels, wheels in lines; this is how many pixels count
+// as a line so both come out the same.
+const PIXELS_PER_LINE: f32 = 20.0;
+
+// Where a button lives in State::now_mouse_buttons and prev_mouse_buttons.
+pub fn mouse_button_index(button: MouseButton) -> Option<usize> {
+    let index = match button {
+        MouseButton::Left => 0,
+        MouseButton::Right => 1,
+        MouseButton::Middle => 2,
+        MouseButton::Other(n) => 3 + n as usize,
+    };
+    if index < MOUSE_BUTTONS {
+        Some(index)
+    } else {
+        None
+    }
+}
+
+// The cursor moving onto or off the topmost drawable under it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HoverEvent {
+    Enter(DrawableId),
+    Leave(DrawableId),
+}
+
+impl State {
+    pub fn mouse_down(&self, button: MouseButton) -> bool {
+        mouse_button_index(button).is_some_and(|i| self.now_mouse_buttons[i])
+    }
+
+    pub fn mouse_was_down(&self, button: MouseButton) -> bool {
+        mouse_button_index(button).is_some_and(|i| self.prev_mouse_buttons[i])
+    }
+
+    pub fn mouse_just_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_down(button) && !self.mouse_was_down(button)
+    }
+
+    pub fn mouse_just_released(&self, button: MouseButton) -> bool {
+        !self.mouse_down(button) && self.mouse_was_down(button)
+    }
+
+    pub fn set_mouse_button(&mut self, button: MouseButton, down: bool) {
+        if let Some(i) = mouse_button_index(button) {
+            self.now_mouse_buttons[i] = down;
+        }
+    }
+
+    // Adds a winit scroll to this frame's wheel_delta.
+    pub fn scroll(&mut self, delta: MouseScrollDelta) {
+        let (x, y) = match delta {
+            MouseScrollDelta::LineDelta(x, y) => (x, y),
+            MouseScrollDelta::PixelDelta(p) => {
+                (p.x as f32 / PIXELS_PER_LINE, p.y as f32 / PIXELS_PER_LINE)
+            }
+        };
+        self.wheel_delta.0 += x;
+        self.wheel_delta.1 += y;
+    }
+}
+
+// Works out which drawable the cursor is over (the topmost one, same as
+// clicking would pick) and reports the change since the last call.
+// handle_winit_event calls it whenever the cursor moves and collects what it
+// says in state.hover_events; call it yourself after moving drawables under a
+// cursor that stayed put.
+//
+// Hover follows ids, so a game that rebuilds state.drawables every frame gets
+// a leave and an enter every frame.
+pub fn update_hover(state: &mut State) -> Vec<HoverEvent> {
+    let hovered = state
+        .drawables
+        .draw_order(state.drag_item_id)
+        .into_iter()
+        .rev()
+        .find(|&id| state.drawables[id].contains(state.mouse_coords));
+
+    let mut events = vec![];
+    if hovered != state.hovered {
+        if let Some(old) = state.hovered {
+            events.push(HoverEvent::Leave(old));
+        }
+        if let Some(new) = hovered {
+            events.push(HoverEvent::Enter(new));
+        }
+        state.hovered = hovered;
+    }
+    events
+}
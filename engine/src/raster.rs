@@ -0,0 +1,182 @@
+use crate::{Color, Rect};
+use std::cmp::{max, min};
+
+// A framebuffer slice plus the logical size it was allocated with, so the
+// primitives don't have to assume a resolution.
+//
+// Everything drawn through a canvas is clipped to the screen and to the top of
+// the clip stack, so shapes hanging off an edge are cut off instead of wrapping
+// into the next row or panicking.
+pub struct Canvas<'a> {
+    pub fb: &'a mut [Color],
+    pub width: usize,
+    pub height: usize,
+    clip_stack: Vec<Rect>,
+}
+
+impl<'a> Canvas<'a> {
+    pub fn new(fb: &'a mut [Color], width: usize, height: usize) -> Canvas<'a> {
+        assert_eq!(fb.len(), width * height);
+        Canvas {
+            fb,
+            width,
+            height,
+            clip_stack: vec![],
+        }
+    }
+
+    // The area drawing is currently allowed to touch.
+    pub fn clip_rect(&self) -> Rect {
+        match self.clip_stack.last() {
+            Some(r) => *r,
+            None => Rect::new(0, 0, self.width, self.height),
+        }
+    }
+
+    // Nested clips intersect, so a child can never draw outside its parent.
+    pub fn push_clip(&mut self, r: Rect) {
+        let clip = self.clip_rect().intersect(&r);
+        self.clip_stack.push(clip);
+    }
+
+    // Popping more than was pushed just leaves the screen as the clip.
+    pub fn pop_clip(&mut self) {
+        self.clip_stack.pop();
+    }
+
+    pub fn with_clip<F: FnOnce(&mut Canvas<'a>)>(&mut self, r: Rect, f: F) {
+        self.push_clip(r);
+        f(self);
+        self.pop_clip();
+    }
+}
+
+// Source-over compositing of src onto dst. Colors are straight (not premultiplied)
+// alpha and the framebuffer is treated as opaque, which it is once it's been cleared.
+pub fn blend(dst: Color, src: Color) -> Color {
+    let a = src.3 as u32;
+    if a == 255 {
+        return src;
+    }
+    if a == 0 {
+        return dst;
+    }
+    let inv = 255 - a;
+    let mix = |s: u8, d: u8| ((s as u32 * a + d as u32 * inv + 127) / 255) as u8;
+    let out_a = a + (dst.3 as u32 * inv + 127) / 255;
+    (mix(src.0, dst.0), mix(src.1, dst.1), mix(src.2, dst.2), out_a as u8)
+}
+
+// Scales a color's alpha by a 0-255 coverage value, e.g. from a glyph bitmap.
+pub fn with_coverage(c: Color, coverage: u8) -> Color {
+    let a = (c.3 as u32 * coverage as u32 + 127) / 255;
+    (c.0, c.1, c.2, a as u8)
+}
+
+pub(crate) fn blend_span(span: &mut [Color], c: Color) {
+    match c.3 {
+        255 => span.fill(c),
+        0 => {}
+        _ => span.iter_mut().for_each(|px| *px = blend(*px, c)),
+    }
+}
+
+// Here's what clear looks like, though we won't use it
+#[allow(dead_code)]
+pub fn clear(fb: &mut [Color], c: Color) {
+    fb.fill(c);
+}
+
+// Overwrites instead of blending, for clearing part of the frame.
+pub(crate) fn fill_rect(canvas: &mut Canvas, r: Rect, c: Color) {
+    let r = r.intersect(&canvas.clip_rect());
+    let w = canvas.width;
+    for y in r.y..r.bottom() {
+        canvas.fb[y * w + r.x..y * w + r.right()].fill(c);
+    }
+}
+
+#[allow(dead_code)]
+pub(crate) fn line(canvas: &mut Canvas, x0: usize, x1: usize, y: usize, c: Color) {
+    let clip = canvas.clip_rect();
+    if y < clip.y || y >= clip.bottom() {
+        return;
+    }
+    let min_x = max(x0, clip.x);
+    let max_x = min(x1, clip.right());
+    if min_x >= max_x {
+        return;
+    }
+    let w = canvas.width;
+    blend_span(&mut canvas.fb[y * w + min_x..(y * w + max_x)], c);
+}
+
+#[allow(dead_code)]
+pub(crate) fn rectangle(canvas: &mut Canvas, r: Rect, c: Color) {
+    let r = r.intersect(&canvas.clip_rect());
+    for i in (r.y)..(r.y + r.h) {
+        line(canvas, r.x, r.x + r.w, i, c);
+    }
+}
+
+#[allow(dead_code)]
+pub(crate) fn rect_outlined(canvas: &mut Canvas, r: Rect, c: Color) {
+    let t = 1;
+    if r.w < 2 * t || r.h < 2 * t {
+        // too thin to have a hole in the middle
+        rectangle(canvas, r, c);
+        return;
+    }
+    let x = r.x;
+    let y = r.y;
+    let h = r.h;
+    let w = r.w;
+    (y..(y + (t))).for_each(|y1| line(canvas, x, x + w, y1, c));
+    ((y + h - t)..(y + h)).for_each(|y1| line(canvas, x, x + w, y1, c));
+
+    ((y + t)..(y + h - (t))).for_each(|y1| {
+        line(canvas, x, x + t, y1, c);
+        line(canvas, x + w - t, x + w, y1, c);
+    });
+}
+
+#[allow(dead_code)]
+pub(crate) fn point(canvas: &mut Canvas, x: usize, y: usize, c: Color) {
+    if !canvas.clip_rect().contains_point(x, y) {
+        return;
+    }
+    let i = y * canvas.width + x;
+    canvas.fb[i] = blend(canvas.fb[i], c);
+}
+
+#[allow(dead_code)]
+pub(crate) fn line_bresenham(
+    canvas: &mut Canvas,
+    (x0, y0): (usize, usize),
+    (x1, y1): (usize, usize),
+    col: Color,
+) {
+    let mut x = x0 as i64;
+    let mut y = y0 as i64;
+    let x0 = x0 as i64;
+    let y0 = y0 as i64;
+    let x1 = x1 as i64;
+    let y1 = y1 as i64;
+    let dx = (x1 - x0).abs();
+    let sx: i64 = if x0 < x1 { 1 } else { -1 };
+    let dy = -(y1 - y0).abs();
+    let sy: i64 = if y0 < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    while x != x1 || y != y1 {
+        point(canvas, x as usize, y as usize, col);
+        let e2 = 2 * err;
+        if dy <= e2 {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
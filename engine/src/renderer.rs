@@ -0,0 +1,507 @@
+// Vulkan backend based on the Vulkano triangle example.
+
+// Triangle example Copyright (c) 2016 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+use crate::{Color, Rect};
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, TypedBufferAccess};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, SubpassContents};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
+use vulkano::device::{Device, DeviceExtensions, Features};
+use vulkano::format::Format;
+use vulkano::image::ImageCreateFlags;
+use vulkano::image::{
+    view::ImageView, ImageAccess, ImageDimensions, ImageUsage, StorageImage, SwapchainImage,
+};
+use vulkano::instance::Instance;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, RenderPass, Subpass};
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+use vulkano::shader::ShaderModule;
+use vulkano::swapchain::{self, AcquireError, Swapchain, SwapchainCreationError};
+use vulkano::sync::{self, FlushError, GpuFuture};
+use vulkano::Version;
+use vulkano_win::VkSurfaceBuild;
+use winit::event_loop::EventLoop;
+use winit::window::{Window, WindowBuilder};
+
+// A renderer takes the finished software framebuffer and puts it somewhere:
+// on screen for the Vulkan backend, nowhere at all for the headless one.
+pub trait Renderer {
+    // Called once per frame after everything has been rasterized into fb2d.
+    // dirty lists the parts of fb that changed since the last present; it's
+    // empty when the frame is identical to the previous one.
+    fn present(&mut self, fb: &[Color], dirty: &[Rect]);
+
+    // The window changed size, so anything tied to the old size is stale.
+    fn resized(&mut self) {}
+
+    // Size of the output surface in physical pixels, used for mouse mapping.
+    fn window_size(&self) -> (f64, f64);
+}
+
+// Keeps the frames in memory and never touches the GPU, so the engine can
+// run on machines without Vulkan or a display (CI, tests, tools).
+pub struct HeadlessRenderer {
+    width: usize,
+    height: usize,
+    frames_presented: usize,
+}
+
+impl HeadlessRenderer {
+    pub fn new(width: usize, height: usize) -> HeadlessRenderer {
+        HeadlessRenderer {
+            width,
+            height,
+            frames_presented: 0,
+        }
+    }
+
+    pub fn frames_presented(&self) -> usize {
+        self.frames_presented
+    }
+}
+
+impl Renderer for HeadlessRenderer {
+    fn present(&mut self, fb: &[Color], _dirty: &[Rect]) {
+        assert_eq!(fb.len(), self.width * self.height);
+        self.frames_presented += 1;
+    }
+
+    fn window_size(&self) -> (f64, f64) {
+        // No window, so the "window" is exactly the framebuffer.
+        (self.width as f64, self.height as f64)
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+struct Vertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+}
+vulkano::impl_vertex!(Vertex, position, uv);
+
+pub struct VulkanRenderer {
+    previous_frame_end: std::option::Option<std::boxed::Box<dyn vulkano::sync::GpuFuture>>,
+    fb_width: usize,
+    recreate_swapchain: bool,
+    fb2d_buffer: Arc<vulkano::buffer::CpuAccessibleBuffer<[(u8, u8, u8, u8)]>>,
+    fb2d_image: std::sync::Arc<vulkano::image::StorageImage>,
+    queue: std::sync::Arc<vulkano::device::Queue>,
+    swapchain: std::sync::Arc<vulkano::swapchain::Swapchain<winit::window::Window>>,
+    viewport: Viewport,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    pipeline: Arc<GraphicsPipeline>,
+    render_pass: Arc<RenderPass>,
+    #[allow(dead_code)]
+    dimensions: ImageDimensions,
+    #[allow(dead_code)]
+    vs: Arc<ShaderModule>,
+    #[allow(dead_code)]
+    fs: Arc<ShaderModule>,
+    surface: std::sync::Arc<vulkano::swapchain::Surface<winit::window::Window>>,
+    device: Arc<vulkano::device::Device>,
+    set: std::sync::Arc<vulkano::descriptor_set::PersistentDescriptorSet>,
+    vertex_buffer: Arc<CpuAccessibleBuffer<[Vertex]>>,
+    window_width: f64,
+    window_height: f64,
+}
+
+fn window_size_dependent_setup(
+    images: &[Arc<SwapchainImage<Window>>],
+    render_pass: Arc<RenderPass>,
+    viewport: &mut Viewport,
+) -> (Vec<Arc<Framebuffer>>, (f64, f64)) {
+    let dimensions = images[0].dimensions().width_height();
+    viewport.dimensions = [dimensions[0] as f32, dimensions[1] as f32];
+    let window_width = dimensions[0].into();
+    let window_height = dimensions[1].into();
+
+    println! {"window width: {:?}", window_width};
+    (
+        images
+            .iter()
+            .map(|image| {
+                let view = ImageView::new(image.clone()).unwrap();
+                Framebuffer::start(render_pass.clone())
+                    .add(view)
+                    .unwrap()
+                    .build()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>(),
+        (window_width, window_height),
+    )
+}
+
+impl VulkanRenderer {
+    // width and height are the size of the software framebuffer, not the window.
+    pub fn new(event_loop: &EventLoop<()>, width: usize, height: usize) -> VulkanRenderer {
+        let required_extensions = vulkano_win::required_extensions();
+        let instance = Instance::new(None, Version::V1_1, &required_extensions, None).unwrap();
+        let surface = WindowBuilder::new()
+            .build_vk_surface(event_loop, instance.clone())
+            .unwrap();
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::none()
+        };
+        let (physical_device, queue_family) = PhysicalDevice::enumerate(&instance)
+            .filter(|&p| p.supported_extensions().is_superset_of(&device_extensions))
+            .filter_map(|p| {
+                p.queue_families()
+                    .find(|&q| q.supports_graphics() && surface.is_supported(q).unwrap_or(false))
+                    .map(|q| (p, q))
+            })
+            .min_by_key(|(p, _)| match p.properties().device_type {
+                PhysicalDeviceType::DiscreteGpu => 0,
+                PhysicalDeviceType::IntegratedGpu => 1,
+                PhysicalDeviceType::VirtualGpu => 2,
+                PhysicalDeviceType::Cpu => 3,
+                PhysicalDeviceType::Other => 4,
+            })
+            .unwrap();
+        let (device, mut queues) = Device::new(
+            physical_device,
+            &Features::none(),
+            &physical_device
+                .required_extensions()
+                .union(&device_extensions),
+            [(queue_family, 0.5)].iter().cloned(),
+        )
+        .unwrap();
+        let queue = queues.next().unwrap();
+        let (swapchain, images) = {
+            let caps = surface.capabilities(physical_device).unwrap();
+            let composite_alpha = caps.supported_composite_alpha.iter().next().unwrap();
+            let format = caps.supported_formats[0].0;
+            let dimensions: [u32; 2] = surface.window().inner_size().into();
+            Swapchain::start(device.clone(), surface.clone())
+                .num_images(caps.min_image_count)
+                .format(format)
+                .dimensions(dimensions)
+                .usage(ImageUsage::color_attachment())
+                .sharing_mode(&queue)
+                .composite_alpha(composite_alpha)
+                .build()
+                .unwrap()
+        };
+
+        // We now create a buffer that will store the shape of our triangl
+
+        let vertex_buffer = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::all(),
+            false,
+            [
+                Vertex {
+                    position: [-1.0, -1.0],
+                    uv: [0.0, 0.0],
+                },
+                Vertex {
+                    position: [3.0, -1.0],
+                    uv: [2.0, 0.0],
+                },
+                Vertex {
+                    position: [-1.0, 3.0],
+                    uv: [0.0, 2.0],
+                },
+            ]
+            .iter()
+            .cloned(),
+        )
+        .unwrap();
+        mod vs {
+            vulkano_shaders::shader! {
+                ty: "vertex",
+                src: "
+                    #version 450
+
+                    layout(location = 0) in vec2 position;
+                    layout(location = 1) in vec2 uv;
+                    layout(location = 0) out vec2 out_uv;
+                    void main() {
+                        gl_Position = vec4(position, 0.0, 1.0);
+                        out_uv = uv;
+                    }
+                "
+            }
+        }
+
+        mod fs {
+            vulkano_shaders::shader! {
+                ty: "fragment",
+                src: "
+                    #version 450
+
+                    layout(set = 0, binding = 0) uniform sampler2D tex;
+                    layout(location = 0) in vec2 uv;
+                    layout(location = 0) out vec4 f_color;
+
+                    void main() {
+                        f_color = texture(tex, uv);
+                    }
+                "
+            }
+        }
+
+        let vs = vs::load(device.clone()).unwrap();
+        let fs = fs::load(device.clone()).unwrap();
+
+        // The 2D framebuffer itself lives on State; we copy it to this GPU buffer every frame.
+        // Then on the GPU, we'll copy it into an Image.
+        let fb2d_buffer = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_source(),
+            false,
+            (0..width * height).map(|_| (255_u8, 0_u8, 0_u8, 0_u8)),
+        )
+        .unwrap();
+        // Let's set up the Image we'll copy into:
+        let dimensions = ImageDimensions::Dim2d {
+            width: width as u32,
+            height: height as u32,
+            array_layers: 1,
+        };
+        //image n GPU
+        let fb2d_image = StorageImage::with_usage(
+            device.clone(),
+            dimensions,
+            Format::R8G8B8A8_UNORM,
+            ImageUsage {
+                // This part is key!
+                transfer_destination: true,
+                sampled: true,
+                storage: true,
+                transfer_source: false,
+                color_attachment: false,
+                depth_stencil_attachment: false,
+                transient_attachment: false,
+                input_attachment: false,
+            },
+            ImageCreateFlags::default(),
+            std::iter::once(queue_family),
+        )
+        .unwrap();
+        // Get a view on it to use as a texture:
+        let fb2d_texture = ImageView::new(fb2d_image.clone()).unwrap();
+
+        let fb2d_sampler = Sampler::new(
+            device.clone(),
+            Filter::Linear,
+            Filter::Linear,
+            MipmapMode::Nearest,
+            SamplerAddressMode::Repeat,
+            SamplerAddressMode::Repeat,
+            SamplerAddressMode::Repeat,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .unwrap();
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    // Pro move: We're going to cover the screen completely. Trust us!
+                    load: DontCare,
+                    store: Store,
+                    format: swapchain.format(),
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {}
+            }
+        )
+        .unwrap();
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(BuffersDefinition::new().vertex::<Vertex>())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .unwrap();
+
+        let layout = pipeline.layout().descriptor_set_layouts().get(0).unwrap();
+        let mut set_builder = PersistentDescriptorSet::start(layout.clone());
+
+        set_builder
+            .add_sampled_image(fb2d_texture, fb2d_sampler)
+            .unwrap();
+
+        let set = set_builder.build().unwrap();
+
+        let mut viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [0.0, 0.0],
+            depth_range: 0.0..1.0,
+        };
+
+        let (framebuffers, (window_width, window_height)) =
+            window_size_dependent_setup(&images, render_pass.clone(), &mut viewport);
+        let recreate_swapchain = false;
+        let previous_frame_end = Some(sync::now(device.clone()).boxed());
+
+        VulkanRenderer {
+            previous_frame_end,
+            fb_width: width,
+            recreate_swapchain,
+            fb2d_buffer,
+            fb2d_image,
+            queue,
+            swapchain,
+            viewport,
+            framebuffers,
+            pipeline,
+            render_pass,
+            dimensions,
+            vs,
+            fs,
+            surface,
+            device,
+            set,
+            vertex_buffer,
+            window_width,
+            window_height,
+        }
+    }
+}
+
+impl Renderer for VulkanRenderer {
+    fn present(&mut self, fb: &[Color], dirty: &[Rect]) {
+        {
+            // We need to synchronize here to send new data to the GPU.
+            // We can't send the new framebuffer until the previous frame is done being drawn.
+            // Dropping the future will block until it's done.
+            if let Some(mut fut) = self.previous_frame_end.take() {
+                fut.cleanup_finished();
+            }
+        }
+
+        // Now we can copy into our buffer. Only the rows of the dirty rects
+        // changed, so a still frame uploads nothing at all.
+        if !dirty.is_empty() {
+            let writable_fb = &mut *self.fb2d_buffer.write().unwrap();
+            let w = self.fb_width;
+            for r in dirty {
+                for y in r.y..r.bottom() {
+                    let row = y * w;
+                    writable_fb[row + r.x..row + r.right()].copy_from_slice(&fb[row + r.x..row + r.right()]);
+                }
+            }
+        }
+
+        if self.recreate_swapchain {
+            let dimensions: [u32; 2] = self.surface.window().inner_size().into();
+            let (new_swapchain, new_images) =
+                match self.swapchain.recreate().dimensions(dimensions).build() {
+                    Ok(r) => r,
+                    Err(SwapchainCreationError::UnsupportedDimensions) => return,
+                    Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
+                };
+
+            self.swapchain = new_swapchain;
+            let setup_result = window_size_dependent_setup(
+                &new_images,
+                self.render_pass.clone(),
+                &mut self.viewport,
+            );
+
+            self.framebuffers = setup_result.0;
+            self.window_width = setup_result.1 .0;
+            self.window_height = setup_result.1 .1;
+            self.recreate_swapchain = false;
+        }
+        let (image_num, suboptimal, acquire_future) =
+            match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(AcquireError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    return;
+                }
+                Err(e) => panic!("Failed to acquire next image: {:?}", e),
+            };
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            self.device.clone(),
+            self.queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        builder
+            // Now copy that framebuffer buffer into the framebuffer image
+            .copy_buffer_to_image(self.fb2d_buffer.clone(), self.fb2d_image.clone())
+            .unwrap()
+            // And resume our regularly scheduled programming
+            .begin_render_pass(
+                self.framebuffers[image_num].clone(),
+                SubpassContents::Inline,
+                std::iter::once(vulkano::format::ClearValue::None),
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                self.set.clone(),
+            )
+            .bind_vertex_buffers(0, self.vertex_buffer.clone())
+            .draw(self.vertex_buffer.len() as u32, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        let future = acquire_future
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_swapchain_present(self.queue.clone(), self.swapchain.clone(), image_num)
+            .then_signal_fence_and_flush();
+
+        match future {
+            Ok(future) => {
+                self.previous_frame_end = Some(future.boxed());
+            }
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                self.previous_frame_end = Some(sync::now(self.device.clone()).boxed());
+            }
+            Err(e) => {
+                println!("Failed to flush future: {:?}", e);
+                self.previous_frame_end = Some(sync::now(self.device.clone()).boxed());
+            }
+        }
+    }
+
+    fn resized(&mut self) {
+        self.recreate_swapchain = true;
+    }
+
+    fn window_size(&self) -> (f64, f64) {
+        (self.window_width, self.window_height)
+    }
+}
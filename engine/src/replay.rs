@@ -0,0 +1,257 @@
+// Recording a session's input so it can be played back exactly. A recording
+// is JSON lines: a header with the RNG seed and resolution, then one line per
+// frame with how long the frame took and what the keys and mouse were doing.
+// It's written a frame at a time, so a crash still leaves everything up to it.
+//
+// Replay only comes out the same if the game gets its randomness from
+// state.rng and its time from state.now() / state.clock rather than the wall
+// clock.
+
+use crate::{update_hover, State, MOUSE_BUTTONS};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const RECORDING_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    seed: u64,
+    width: usize,
+    height: usize,
+}
+
+// The input state at the end of one frame.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FrameInput {
+    // how long the frame took, in microseconds
+    pub dt: u64,
+    // indices into now_keys and now_mouse_buttons of everything held down
+    pub keys: Vec<usize>,
+    pub buttons: Vec<usize>,
+    pub mouse: (usize, usize),
+    pub wheel: (f32, f32),
+}
+
+impl FrameInput {
+    pub fn capture(state: &State) -> FrameInput {
+        let down = |held: &[bool]| {
+            held.iter().enumerate().filter(|(_, &d)| d).map(|(i, _)| i).collect()
+        };
+        FrameInput {
+            dt: state.frame_time.as_micros() as u64,
+            keys: down(&state.now_keys),
+            buttons: down(&state.now_mouse_buttons),
+            mouse: state.mouse_coords,
+            wheel: state.wheel_delta,
+        }
+    }
+
+    // Overwrites the live input with this frame's.
+    pub fn apply(&self, state: &mut State) {
+        state.now_keys = [false; 255];
+        for &key in self.keys.iter().filter(|&&k| k < 255) {
+            state.now_keys[key] = true;
+        }
+        state.now_mouse_buttons = [false; MOUSE_BUTTONS];
+        for &button in self.buttons.iter().filter(|&&b| b < MOUSE_BUTTONS) {
+            state.now_mouse_buttons[button] = true;
+        }
+        state.mouse_coords = self.mouse;
+        state.wheel_delta = self.wheel;
+    }
+}
+
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    fn write_line<T: Serialize>(&mut self, line: &T) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, line)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    pub seed: u64,
+    pub resolution: (usize, usize),
+    pub frames: Vec<FrameInput>,
+}
+
+impl Recording {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Recording> {
+        let invalid = |e: serde_json::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header: Header = match lines.next() {
+            Some(line) => serde_json::from_str(&line?).map_err(invalid)?,
+            None => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "empty recording")),
+        };
+        if header.version != RECORDING_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("recording is version {}, expected {}", header.version, RECORDING_VERSION),
+            ));
+        }
+        let mut frames = vec![];
+        for line in lines {
+            let line = line?;
+            // a crash can cut the last line short
+            match serde_json::from_str(&line) {
+                Ok(frame) => frames.push(frame),
+                Err(_) if line.trim().is_empty() => {}
+                Err(e) if e.is_eof() => break,
+                Err(e) => return Err(invalid(e)),
+            }
+        }
+        Ok(Recording {
+            seed: header.seed,
+            resolution: (header.width, header.height),
+            frames,
+        })
+    }
+}
+
+// A recording being played back.
+pub struct Replay {
+    recording: Recording,
+    next: usize,
+}
+
+impl Replay {
+    pub fn frames_left(&self) -> usize {
+        self.recording.frames.len().saturating_sub(self.next)
+    }
+}
+
+impl State {
+    // Starts over with a new seed; everything drawn from rng after this is the
+    // same every time for the same seed.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    // Game time as an Instant: when the state was set up plus clock. Use it
+    // instead of Instant::now() for anything that has to replay the same.
+    pub fn now(&self) -> Instant {
+        self.started_at + self.clock
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    // Writes every frame from here on to path. Start recording before drawing
+    // anything from rng, since replay starts from the seed.
+    pub fn start_recording<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let mut recorder = Recorder {
+            out: BufWriter::new(File::create(path)?),
+        };
+        recorder.write_line(&Header {
+            version: RECORDING_VERSION,
+            seed: self.seed,
+            width: self.config.width,
+            height: self.config.height,
+        })?;
+        self.recorder = Some(recorder);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    // Replaces live input with the recording's, starting next frame, and
+    // reseeds rng with the seed it was recorded with. Live keyboard and mouse
+    // events are ignored until it runs out.
+    pub fn start_replay<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let recording = Recording::load(path)?;
+        let (width, height) = recording.resolution;
+        if recording.resolution != self.screen_size() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "recorded at {}x{}, running at {}x{}",
+                    width,
+                    height,
+                    self.width(),
+                    self.height()
+                ),
+            ));
+        }
+        self.reseed(recording.seed);
+        self.replay = Some(Replay { recording, next: 0 });
+        Ok(())
+    }
+
+    // ENGINE_RECORD=<file> records the session and ENGINE_REPLAY=<file> plays
+    // one back. Call it right after setup.
+    pub fn record_or_replay_from_env(&mut self) -> io::Result<()> {
+        if let Ok(path) = std::env::var("ENGINE_REPLAY") {
+            self.start_replay(&path)?;
+            println!("replaying {}", path);
+        } else if let Ok(path) = std::env::var("ENGINE_RECORD") {
+            self.start_recording(&path)?;
+            println!("recording to {} (seed {})", path, self.seed);
+        }
+        Ok(())
+    }
+}
+
+// Moves on to the next frame: saves the one that just ended if recording,
+// carries the input over into prev_*, and works out how long this frame is.
+// handle_winit_event does this on every NewEvents; headless code that runs
+// frames itself should call it once per frame.
+pub fn begin_frame(state: &mut State) {
+    if state.frame > 0 {
+        let input = FrameInput::capture(state);
+        if let Some(recorder) = state.recorder.as_mut() {
+            if let Err(e) = recorder.write_line(&input) {
+                println!("Failed to record frame {}, recording stopped: {:?}", state.frame, e);
+                state.recorder = None;
+            }
+        }
+    }
+
+    // Leave now_keys alone, but copy over all changed keys
+    state.prev_keys.copy_from_slice(&state.now_keys);
+    state.prev_mouse_buttons.copy_from_slice(&state.now_mouse_buttons);
+    state.prev_mouse_coords = state.mouse_coords;
+    state.wheel_delta = (0.0, 0.0);
+    state.hover_events.clear();
+
+    let wall_clock = Instant::now();
+    let replayed = state.replay.as_mut().and_then(|replay| {
+        let frame = replay.recording.frames.get(replay.next).cloned();
+        replay.next += 1;
+        frame
+    });
+    state.frame_time = match replayed {
+        Some(frame) => {
+            frame.apply(state);
+            // the cursor jumps straight to where it was, there's no CursorMoved
+            state.hover_events = update_hover(state);
+            Duration::from_micros(frame.dt)
+        }
+        None => {
+            if state.replay.take().is_some() {
+                println!("replay finished after {} frames, back to live input", state.frame);
+            }
+            // whole microseconds, same as a recording stores, so the clock adds
+            // up to exactly the same thing on replay
+            let dt = state.last_frame_at.map_or(Duration::ZERO, |last| wall_clock - last);
+            Duration::from_micros(dt.as_micros() as u64)
+        }
+    };
+    state.last_frame_at = Some(wall_clock);
+    state.clock += state.frame_time;
+    state.frame += 1;
+}
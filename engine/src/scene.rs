@@ -0,0 +1,122 @@
+use crate::{Color, Drawable, Rect};
+
+// Past this many separate dirty rects it's cheaper to just repaint everything.
+const MAX_DIRTY_RECTS: usize = 16;
+
+// The frame as it was last rasterized: a copy of every drawable, in the order
+// they were painted, and the area each one painted. Each draw() diffs the
+// sorted state.drawables against it, so only drawables that actually changed
+// get cloned, and only the regions they cover (before and after) get
+// repainted. A board where nothing moves costs a compare per drawable and
+// nothing else.
+pub struct Scene {
+    items: Vec<Drawable>,
+    bounds: Vec<Rect>,
+    bg_color: Option<Color>,
+    font_generation: Option<usize>,
+    screen: Rect,
+}
+
+impl Scene {
+    pub fn new(width: usize, height: usize) -> Scene {
+        Scene {
+            items: vec![],
+            bounds: vec![],
+            bg_color: None,
+            font_generation: None,
+            screen: Rect::new(0, 0, width, height),
+        }
+    }
+
+    // Forces the next frame to be repainted from scratch, e.g. after writing
+    // into fb2d by hand.
+    pub fn invalidate(&mut self) {
+        self.bg_color = None;
+    }
+
+    // Invalidates if the fonts changed since the last frame. Text drawn with a
+    // font that's been replaced looks different without the drawable changing.
+    pub fn check_fonts(&mut self, generation: usize) {
+        if self.font_generation != Some(generation) {
+            self.font_generation = Some(generation);
+            self.invalidate();
+        }
+    }
+
+    pub fn items(&self) -> &[Drawable] {
+        &self.items
+    }
+
+    pub fn bounds(&self) -> &[Rect] {
+        &self.bounds
+    }
+
+    // Brings the retained copy up to date and returns the regions of the screen
+    // that need repainting. drawables come in paint order, back to front, and
+    // bounds_of says where a drawable paints.
+    pub fn update<'a, I, F>(&mut self, drawables: I, bg_color: Color, mut bounds_of: F) -> Vec<Rect>
+    where
+        I: IntoIterator<Item = &'a Drawable>,
+        F: FnMut(&Drawable) -> Rect,
+    {
+        if self.bg_color != Some(bg_color) {
+            self.bg_color = Some(bg_color);
+            self.items = drawables.into_iter().cloned().collect();
+            self.bounds = self.items.iter().map(&mut bounds_of).collect();
+            return vec![self.screen];
+        }
+
+        let mut dirty = vec![];
+        let mut count = 0;
+        for (i, new) in drawables.into_iter().enumerate() {
+            count += 1;
+            if let Some(old) = self.items.get(i) {
+                if old == new {
+                    continue;
+                }
+                dirty.push(self.bounds[i]);
+            }
+            let b = bounds_of(new);
+            dirty.push(b);
+            if i < self.items.len() {
+                self.items[i] = new.clone();
+                self.bounds[i] = b;
+            } else {
+                self.items.push(new.clone());
+                self.bounds.push(b);
+            }
+        }
+        if self.items.len() > count {
+            dirty.extend_from_slice(&self.bounds[count..]);
+            self.items.truncate(count);
+            self.bounds.truncate(count);
+        }
+
+        let dirty = merge_rects(dirty.iter().map(|r| r.intersect(&self.screen)).collect());
+        let area: usize = dirty.iter().map(|r| r.w * r.h).sum();
+        if dirty.len() > MAX_DIRTY_RECTS || area > self.screen.w * self.screen.h / 2 {
+            return vec![self.screen];
+        }
+        dirty
+    }
+}
+
+// Drops empty rects and merges any that overlap, until none do.
+fn merge_rects(mut rects: Vec<Rect>) -> Vec<Rect> {
+    rects.retain(|r| !r.is_empty());
+    let mut merged = true;
+    while merged {
+        merged = false;
+        'outer: for i in 0..rects.len() {
+            for j in (i + 1)..rects.len() {
+                if !rects[i].intersect(&rects[j]).is_empty() {
+                    let other = rects.swap_remove(j);
+                    rects[i] = rects[i].union(&other);
+                    merged = true;
+                    break 'outer;
+                }
+            }
+        }
+    }
+    rects
+}
@@ -0,0 +1,88 @@
+use crate::{Color, State};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Encodes a framebuffer as an 8-bit RGB PNG into any writer.
+// Alpha is dropped on purpose: the window ignores it, so the capture should too.
+pub fn write_png<W: Write>(out: W, fb: &[Color], width: usize, height: usize) -> io::Result<()> {
+    assert_eq!(fb.len(), width * height);
+
+    let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+
+    let data: Vec<u8> = fb.iter().flat_map(|&(r, g, b, _)| [r, g, b]).collect();
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(())
+}
+
+pub fn encode_png(fb: &[Color], width: usize, height: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    write_png(&mut bytes, fb, width, height)?;
+    Ok(bytes)
+}
+
+// Decodes a PNG into framebuffer pixels plus its size. Palette, grayscale and
+// 16-bit images are expanded to 8-bit channels, so exported art just works.
+pub fn load_png<P: AsRef<Path>>(path: P) -> io::Result<(Vec<Color>, usize, usize)> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    let bytes = &buf[..info.buffer_size()];
+
+    if info.bit_depth != png::BitDepth::Eight {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported PNG bit depth {:?}", info.bit_depth),
+        ));
+    }
+    let pixels = match info.color_type {
+        png::ColorType::Rgb => bytes.chunks(3).map(|p| (p[0], p[1], p[2], 255)).collect(),
+        png::ColorType::Rgba => bytes.chunks(4).map(|p| (p[0], p[1], p[2], p[3])).collect(),
+        png::ColorType::Grayscale => bytes.iter().map(|&g| (g, g, g, 255)).collect(),
+        png::ColorType::GrayscaleAlpha => bytes.chunks(2).map(|p| (p[0], p[0], p[0], p[1])).collect(),
+        other => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported PNG color type {:?}", other),
+            ))
+        }
+    };
+    Ok((pixels, info.width as usize, info.height as usize))
+}
+
+pub fn save_png<P: AsRef<Path>>(path: P, fb: &[Color], width: usize, height: usize) -> io::Result<()> {
+    let file = File::create(path)?;
+    write_png(BufWriter::new(file), fb, width, height)
+}
+
+impl State {
+    // The last frame drawn, as PNG bytes.
+    pub fn screenshot_png(&self) -> io::Result<Vec<u8>> {
+        encode_png(&self.fb2d, self.width(), self.height())
+    }
+
+    pub fn save_screenshot<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        save_png(path, &self.fb2d, self.width(), self.height())
+    }
+
+    // Saves into screenshot_dir as screenshot-<unix millis>.png and returns the path.
+    pub fn save_timestamped_screenshot(&self) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.screenshot_dir)?;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let path = self
+            .screenshot_dir
+            .join(format!("screenshot-{}.png", millis));
+        self.save_screenshot(&path)?;
+        Ok(path)
+    }
+}
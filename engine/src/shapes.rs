@@ -0,0 +1,248 @@
+use crate::raster::{blend, with_coverage, Canvas};
+use crate::{Color, FbCoords, Rect};
+
+// How a vector shape is painted: filled, or just its edge stroked `width`
+// pixels thick. Strokes sit inside the shape, like rect_outlined, so a shape
+// never paints outside its bounds.
+//
+// Shapes are aliased by default, which keeps pixel art crisp. Anti-aliased
+// ones blend their edge pixels by how much of each pixel the shape covers.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShapeStyle {
+    pub color: Color,
+    pub outline: Option<usize>,
+    pub anti_alias: bool,
+}
+
+impl ShapeStyle {
+    pub fn filled(color: Color) -> ShapeStyle {
+        ShapeStyle {
+            color,
+            outline: None,
+            anti_alias: false,
+        }
+    }
+
+    pub fn outlined(color: Color, width: usize) -> ShapeStyle {
+        ShapeStyle {
+            color,
+            outline: Some(width),
+            anti_alias: false,
+        }
+    }
+
+    pub fn anti_aliased(self) -> ShapeStyle {
+        ShapeStyle {
+            anti_alias: true,
+            ..self
+        }
+    }
+}
+
+// Shapes in continuous pixel space, where pixel (x, y) covers x..x+1, y..y+1.
+// Each one is drawn by asking, for every pixel center near it, how far it is
+// from the shape's edge (negative inside), so filling, stroking and hit
+// testing all come from one function per shape.
+#[derive(Clone, Debug)]
+pub(crate) enum Shape {
+    // round-capped segment between two points
+    Segment((f32, f32), (f32, f32), f32),
+    // center and radii
+    Ellipse((f32, f32), (f32, f32)),
+    // vertices of a convex polygon, either winding
+    Polygon(Vec<(f32, f32)>),
+    // center, half size and corner radius
+    RoundedBox((f32, f32), (f32, f32), f32),
+}
+
+fn center_of((x, y): FbCoords) -> (f32, f32) {
+    (x as f32 + 0.5, y as f32 + 0.5)
+}
+
+impl Shape {
+    // A line of the given thickness between the centers of two pixels.
+    pub(crate) fn line(from: FbCoords, to: FbCoords, thickness: usize) -> Shape {
+        Shape::Segment(center_of(from), center_of(to), thickness as f32 / 2.0)
+    }
+
+    // The ellipse that fills r; a circle when r is square.
+    pub(crate) fn ellipse(r: Rect) -> Shape {
+        let (rx, ry) = (r.w as f32 / 2.0, r.h as f32 / 2.0);
+        Shape::Ellipse((r.x as f32 + rx, r.y as f32 + ry), (rx, ry))
+    }
+
+    // Vertices are pixel centers. Only convex polygons come out right.
+    pub(crate) fn polygon(points: &[FbCoords]) -> Shape {
+        Shape::Polygon(points.iter().copied().map(center_of).collect())
+    }
+
+    pub(crate) fn rounded_rect(r: Rect, radius: usize) -> Shape {
+        let half = (r.w as f32 / 2.0, r.h as f32 / 2.0);
+        let radius = (radius as f32).min(half.0).min(half.1);
+        Shape::RoundedBox((r.x as f32 + half.0, r.y as f32 + half.1), half, radius)
+    }
+
+    // Signed distance from p to the edge: negative inside, positive outside.
+    // Exact for everything but ellipses, which are close enough near the edge.
+    pub(crate) fn distance(&self, (px, py): (f32, f32)) -> f32 {
+        match *self {
+            Shape::Segment((ax, ay), (bx, by), half_width) => {
+                let (dx, dy) = (bx - ax, by - ay);
+                let len2 = dx * dx + dy * dy;
+                let t = if len2 == 0.0 {
+                    0.0
+                } else {
+                    (((px - ax) * dx + (py - ay) * dy) / len2).clamp(0.0, 1.0)
+                };
+                let (ex, ey) = (ax + t * dx - px, ay + t * dy - py);
+                (ex * ex + ey * ey).sqrt() - half_width
+            }
+            Shape::Ellipse((cx, cy), (rx, ry)) => {
+                if rx <= 0.0 || ry <= 0.0 {
+                    return f32::INFINITY;
+                }
+                let (dx, dy) = (px - cx, py - cy);
+                if rx == ry {
+                    return (dx * dx + dy * dy).sqrt() - rx;
+                }
+                // f / |grad f| for f = (x/rx)^2 + (y/ry)^2 - 1
+                let f = (dx / rx).powi(2) + (dy / ry).powi(2) - 1.0;
+                let (gx, gy) = (2.0 * dx / (rx * rx), 2.0 * dy / (ry * ry));
+                let grad = (gx * gx + gy * gy).sqrt();
+                if grad == 0.0 {
+                    -rx.min(ry)
+                } else {
+                    f / grad
+                }
+            }
+            Shape::Polygon(ref points) => {
+                if points.len() < 3 {
+                    return f32::INFINITY;
+                }
+                // the furthest outside of all the edges' half-planes, which is
+                // exact inside and only rounds off corners outside
+                let winding = signed_area(points).signum();
+                let mut distance = f32::NEG_INFINITY;
+                for (i, &(ax, ay)) in points.iter().enumerate() {
+                    let (bx, by) = points[(i + 1) % points.len()];
+                    let (ex, ey) = (bx - ax, by - ay);
+                    let len = (ex * ex + ey * ey).sqrt();
+                    if len == 0.0 {
+                        continue;
+                    }
+                    // positive on the outside of the edge
+                    let side = winding * ((px - ax) * ey - (py - ay) * ex) / len;
+                    distance = distance.max(side);
+                }
+                distance
+            }
+            Shape::RoundedBox((cx, cy), (hw, hh), radius) => {
+                let qx = (px - cx).abs() - (hw - radius);
+                let qy = (py - cy).abs() - (hh - radius);
+                let outside = (qx.max(0.0).powi(2) + qy.max(0.0).powi(2)).sqrt();
+                outside + qx.max(qy).min(0.0) - radius
+            }
+        }
+    }
+
+    // Every pixel the shape could touch.
+    pub(crate) fn bounds(&self) -> Rect {
+        let (left, top, right, bottom) = match *self {
+            Shape::Segment((ax, ay), (bx, by), half_width) => (
+                ax.min(bx) - half_width,
+                ay.min(by) - half_width,
+                ax.max(bx) + half_width,
+                ay.max(by) + half_width,
+            ),
+            Shape::Ellipse((cx, cy), (rx, ry)) => (cx - rx, cy - ry, cx + rx, cy + ry),
+            Shape::Polygon(ref points) => points.iter().fold(
+                (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+                |(l, t, r, b), &(x, y)| (l.min(x), t.min(y), r.max(x), b.max(y)),
+            ),
+            Shape::RoundedBox((cx, cy), (hw, hh), _) => (cx - hw, cy - hh, cx + hw, cy + hh),
+        };
+        if left > right || top > bottom {
+            return Rect::new(0, 0, 0, 0);
+        }
+        let (left, top) = (left.floor().max(0.0), top.floor().max(0.0));
+        let (right, bottom) = (right.ceil().max(left), bottom.ceil().max(top));
+        Rect::new(
+            left as usize,
+            top as usize,
+            (right - left) as usize,
+            (bottom - top) as usize,
+        )
+    }
+
+    // Whether the pixel is painted when the shape is drawn with style.
+    pub(crate) fn covers(&self, (x, y): FbCoords, style: &ShapeStyle) -> bool {
+        stroked(self.distance(center_of((x, y))), style) <= 0.0
+    }
+}
+
+fn signed_area(points: &[(f32, f32)]) -> f32 {
+    let mut area = 0.0;
+    for (i, &(ax, ay)) in points.iter().enumerate() {
+        let (bx, by) = points[(i + 1) % points.len()];
+        area += ax * by - bx * ay;
+    }
+    area / 2.0
+}
+
+// Turns the distance to a shape's edge into the distance to its stroke, which
+// is the band just inside the edge.
+fn stroked(distance: f32, style: &ShapeStyle) -> f32 {
+    match style.outline {
+        Some(width) => distance.max(-distance - width as f32),
+        None => distance,
+    }
+}
+
+// Paints every pixel whose center is inside the shape (or its stroke). With
+// anti-aliasing, a pixel's coverage is estimated from how far its center is
+// from the edge: half a pixel inside is fully covered, half outside not at all.
+// That never reaches past the pixels shape.bounds() already includes.
+pub(crate) fn draw_shape(canvas: &mut Canvas, shape: &Shape, style: &ShapeStyle) {
+    let area = shape.bounds().intersect(&canvas.clip_rect());
+    for y in area.y..area.bottom() {
+        let row = y * canvas.width;
+        for x in area.x..area.right() {
+            let distance = stroked(shape.distance(center_of((x, y))), style);
+            let c = if style.anti_alias {
+                let coverage = (0.5 - distance).clamp(0.0, 1.0);
+                with_coverage(style.color, (coverage * 255.0).round() as u8)
+            } else if distance <= 0.0 {
+                style.color
+            } else {
+                continue;
+            };
+            canvas.fb[row + x] = blend(canvas.fb[row + x], c);
+        }
+    }
+}
+
+pub fn draw_line(canvas: &mut Canvas, from: FbCoords, to: FbCoords, thickness: usize, style: &ShapeStyle) {
+    draw_shape(canvas, &Shape::line(from, to, thickness), style);
+}
+
+pub fn draw_ellipse(canvas: &mut Canvas, r: Rect, style: &ShapeStyle) {
+    draw_shape(canvas, &Shape::ellipse(r), style);
+}
+
+pub fn draw_polygon(canvas: &mut Canvas, points: &[FbCoords], style: &ShapeStyle) {
+    draw_shape(canvas, &Shape::polygon(points), style);
+}
+
+pub fn draw_rounded_rect(canvas: &mut Canvas, r: Rect, radius: usize, style: &ShapeStyle) {
+    draw_shape(canvas, &Shape::rounded_rect(r, radius), style);
+}
+
+// The square that circle with this center and radius is drawn in.
+pub fn circle_rect((x, y): FbCoords, radius: usize) -> Rect {
+    Rect::new(
+        x.saturating_sub(radius),
+        y.saturating_sub(radius),
+        radius * 2 + 1,
+        radius * 2 + 1,
+    )
+}
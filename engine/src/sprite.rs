@@ -0,0 +1,122 @@
+use crate::raster::{blend, with_coverage, Canvas};
+use crate::screenshot::load_png;
+use crate::{Color, Rect};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+// Decoded pixels, straight alpha like the framebuffer.
+pub struct Image {
+    pub pixels: Vec<Color>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Image {
+    pub fn new(pixels: Vec<Color>, width: usize, height: usize) -> Image {
+        assert_eq!(pixels.len(), width * height);
+        Image {
+            pixels,
+            width,
+            height,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Image> {
+        let (pixels, width, height) = load_png(path)?;
+        Ok(Image::new(pixels, width, height))
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+}
+
+// Printing every pixel whenever a drawable gets dbg!'d isn't useful.
+impl fmt::Debug for Image {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Image({}x{})", self.width, self.height)
+    }
+}
+
+// A view into an image: which part of it to draw (the whole thing, or one cell of
+// a sprite sheet) and how opaque. The image is shared, so cloning the drawable
+// every frame doesn't copy pixels.
+#[derive(Clone, Debug)]
+pub struct Sprite {
+    pub image: Rc<Image>,
+    pub src: Rect,
+    pub opacity: u8,
+}
+
+// Two sprites are the same if they show the same part of the same image; the
+// pixels themselves are never compared.
+impl PartialEq for Sprite {
+    fn eq(&self, other: &Sprite) -> bool {
+        Rc::ptr_eq(&self.image, &other.image) && self.src == other.src && self.opacity == other.opacity
+    }
+}
+
+impl Sprite {
+    pub fn new(image: Rc<Image>) -> Sprite {
+        let src = image.bounds();
+        Sprite {
+            image,
+            src,
+            opacity: 255,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Sprite> {
+        Ok(Sprite::new(Rc::new(Image::load(path)?)))
+    }
+
+    // Crops to a sub-rect of the image, e.g. one frame of a sprite sheet.
+    pub fn with_src(self, src: Rect) -> Sprite {
+        assert!(
+            src.x + src.w <= self.image.width && src.y + src.h <= self.image.height,
+            "sprite src {:?} is outside the {:?}",
+            src,
+            self.image
+        );
+        Sprite { src, ..self }
+    }
+
+    // Cell (col, row) of a sheet laid out in a grid of cell_w x cell_h frames.
+    pub fn sheet_cell(self, cell_w: usize, cell_h: usize, col: usize, row: usize) -> Sprite {
+        self.with_src(Rect::new(col * cell_w, row * cell_h, cell_w, cell_h))
+    }
+
+    pub fn with_opacity(self, opacity: u8) -> Sprite {
+        Sprite { opacity, ..self }
+    }
+
+    // Where to put the sprite so it draws unscaled.
+    pub fn natural_rect(&self, x: usize, y: usize) -> Rect {
+        Rect::new(x, y, self.src.w, self.src.h)
+    }
+}
+
+// Draws the sprite's src rect stretched over dst with nearest-neighbour sampling,
+// so pixel art stays crisp. Each texel is blended with its own alpha scaled by the
+// sprite's opacity.
+pub fn draw_sprite(canvas: &mut Canvas, sprite: &Sprite, dst: Rect) {
+    let src = sprite.src;
+    if src.w == 0 || src.h == 0 || dst.w == 0 || dst.h == 0 {
+        return;
+    }
+    let image = &sprite.image;
+
+    let visible = dst.intersect(&canvas.clip_rect());
+    for y in visible.y..visible.bottom() {
+        let sy = src.y + (y - dst.y) * src.h / dst.h;
+        let src_row = &image.pixels[sy * image.width..(sy + 1) * image.width];
+        let dst_row = y * canvas.width;
+        for x in visible.x..visible.right() {
+            let sx = src.x + (x - dst.x) * src.w / dst.w;
+            let texel = with_coverage(src_row[sx], sprite.opacity);
+            canvas.fb[dst_row + x] = blend(canvas.fb[dst_row + x], texel);
+        }
+    }
+}
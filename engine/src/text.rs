@@ -0,0 +1,996 @@
+use crate::glyph_cache::{GlyphCache, PlacedGlyph};
+use crate::markup::parse_markup;
+use crate::raster::{blend, with_coverage, Canvas};
+use crate::fonts::{FontId, FontRegistry};
+use crate::{Color, Rect};
+use fontdue::layout::{
+    CoordinateSystem, HorizontalAlign, Layout, LayoutSettings, TextStyle as FontdueStyle,
+    VerticalAlign, WrapStyle,
+};
+use fontdue::{Font, Metrics};
+use std::ops::Range;
+use std::rc::Rc;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum HAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VAlign {
+    Top,
+    Middle,
+    Bottom,
+}
+
+// Off keeps every line on one row, however wide it gets. Hard breaks ('\n')
+// always start a new line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Wrap {
+    Word,
+    Letter,
+    Off,
+}
+
+// Shrinks text until it fits its rect instead of letting it overflow. The
+// drawable's size is the largest it'll try; if it still doesn't fit at
+// min_size, the end is cut off and replaced with an ellipsis (if enabled).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextFit {
+    pub min_size: f32,
+    pub ellipsis: bool,
+}
+
+// Small stat icons drawn inline with text. They're drawn procedurally, in
+// their own colors, so they don't depend on what glyphs a font has.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Icon {
+    Hp,
+    Defense,
+    Attack,
+    Mana,
+}
+
+impl Icon {
+    // The markup tag name, e.g. "hp" for [hp].
+    pub fn from_name(name: &str) -> Option<Icon> {
+        match name {
+            "hp" => Some(Icon::Hp),
+            "def" => Some(Icon::Defense),
+            "atk" => Some(Icon::Attack),
+            "mana" => Some(Icon::Mana),
+            _ => None,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Icon::Hp => (220, 50, 50, 255),
+            Icon::Defense => (150, 170, 190, 255),
+            Icon::Attack => (230, 140, 40, 255),
+            Icon::Mana => (70, 130, 255, 255),
+        }
+    }
+
+    // Whether the point (u, v) of the icon's unit square is inside the shape,
+    // v pointing down.
+    fn covers(self, u: f32, v: f32) -> bool {
+        match self {
+            Icon::Hp => {
+                // the classic heart curve, (x^2 + y^2 - 1)^3 - x^2 y^3 <= 0
+                let (x, y) = ((u - 0.5) * 2.5, 1.25 - v * 2.3);
+                let a = x * x + y * y - 1.0;
+                a * a * a - x * x * y * y * y <= 0.0
+            }
+            Icon::Defense => {
+                // flat top, straight sides, curving in to a point at the bottom
+                let t = ((v - 0.4) / 0.55).max(0.0);
+                (0.06..=0.95).contains(&v) && (u - 0.5).abs() <= 0.42 * (1.0 - t * t)
+            }
+            Icon::Attack => {
+                let near = |(x0, y0): (f32, f32), (x1, y1): (f32, f32), r: f32| {
+                    let (dx, dy) = (x1 - x0, y1 - y0);
+                    let t = (((u - x0) * dx + (v - y0) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+                    let (px, py) = (x0 + t * dx - u, y0 + t * dy - v);
+                    px * px + py * py <= r * r
+                };
+                // blade, crossguard, grip
+                near((0.3, 0.7), (0.88, 0.12), 0.08)
+                    || near((0.16, 0.56), (0.44, 0.84), 0.06)
+                    || near((0.1, 0.9), (0.3, 0.7), 0.05)
+            }
+            Icon::Mana => {
+                // a drop: a circle with a cone on top
+                let (cx, cy, r) = (0.5, 0.64, 0.3);
+                let (dx, dy) = (u - cx, v - cy);
+                dx * dx + dy * dy <= r * r
+                    || ((0.04..cy).contains(&v) && dx.abs() <= r * (v - 0.04) / (cy - 0.04))
+            }
+        }
+    }
+
+    // A side x side coverage bitmap of the icon, 4x4 supersampled so the
+    // edges are smooth like the glyphs around it.
+    pub(crate) fn rasterize(self, side: usize) -> Vec<u8> {
+        let mut bitmap = vec![0; side * side];
+        for (i, coverage) in bitmap.iter_mut().enumerate() {
+            let (x, y) = ((i % side) as f32, (i / side) as f32);
+            let mut hits = 0;
+            for sy in 0..4 {
+                for sx in 0..4 {
+                    let u = (x + (sx as f32 + 0.5) / 4.0) / side as f32;
+                    let v = (y + (sy as f32 + 0.5) / 4.0) / side as f32;
+                    hits += self.covers(u, v) as u32;
+                }
+            }
+            *coverage = (hits * 255 / 16) as u8;
+        }
+        bitmap
+    }
+}
+
+// Overrides how a byte range of the string looks. Anything left as None (or
+// false) comes from the text's own style.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextSpan {
+    pub range: Range<usize>,
+    pub size: Option<f32>,
+    pub font: Option<FontId>,
+    pub color: Option<Color>,
+    pub bold: bool,
+    // Draws the icon in place of the range's text, which should be a single
+    // placeholder character to reserve the room for it.
+    pub icon: Option<Icon>,
+}
+
+impl TextSpan {
+    pub fn new(range: Range<usize>) -> TextSpan {
+        TextSpan {
+            range,
+            size: None,
+            font: None,
+            color: None,
+            bold: false,
+            icon: None,
+        }
+    }
+}
+
+// How a piece of text is painted and laid out. The outline is drawn by stamping
+// the glyph around itself `width` pixels out, the shadow is one extra stamp at
+// an offset. Alignment is within the drawable's rect, and line_spacing is a
+// multiple of each line's natural height. With markup on, the string is parsed
+// with parse_markup before layout, and spans index the text it produces.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextStyle {
+    pub color: Color,
+    pub outline: Option<(Color, usize)>,
+    pub shadow: Option<(Color, (i32, i32))>,
+    pub h_align: HAlign,
+    pub v_align: VAlign,
+    pub wrap: Wrap,
+    pub line_spacing: f32,
+    pub spans: Vec<TextSpan>,
+    pub fit: Option<TextFit>,
+    pub markup: bool,
+}
+
+impl Default for TextStyle {
+    fn default() -> TextStyle {
+        TextStyle {
+            color: (255, 255, 255, 255),
+            outline: None,
+            shadow: None,
+            h_align: HAlign::Left,
+            v_align: VAlign::Top,
+            wrap: Wrap::Word,
+            line_spacing: 1.0,
+            spans: vec![],
+            fit: None,
+            markup: false,
+        }
+    }
+}
+
+impl TextStyle {
+    pub fn new(color: Color) -> TextStyle {
+        TextStyle {
+            color,
+            ..TextStyle::default()
+        }
+    }
+
+    pub fn with_outline(self, color: Color, width: usize) -> TextStyle {
+        TextStyle {
+            outline: Some((color, width)),
+            ..self
+        }
+    }
+
+    pub fn with_shadow(self, color: Color, offset: (i32, i32)) -> TextStyle {
+        TextStyle {
+            shadow: Some((color, offset)),
+            ..self
+        }
+    }
+
+    pub fn aligned(self, h_align: HAlign, v_align: VAlign) -> TextStyle {
+        TextStyle {
+            h_align,
+            v_align,
+            ..self
+        }
+    }
+
+    pub fn with_wrap(self, wrap: Wrap) -> TextStyle {
+        TextStyle { wrap, ..self }
+    }
+
+    pub fn with_line_spacing(self, line_spacing: f32) -> TextStyle {
+        TextStyle {
+            line_spacing,
+            ..self
+        }
+    }
+
+    pub fn fit_to_rect(self, min_size: f32) -> TextStyle {
+        TextStyle {
+            fit: Some(TextFit {
+                min_size,
+                ellipsis: true,
+            }),
+            ..self
+        }
+    }
+
+    pub fn with_markup(self) -> TextStyle {
+        TextStyle {
+            markup: true,
+            ..self
+        }
+    }
+
+    // Later spans win where they overlap earlier ones.
+    pub fn with_span(self, range: Range<usize>, size: Option<f32>, font: Option<FontId>) -> TextStyle {
+        self.with_styled_span(TextSpan {
+            size,
+            font,
+            ..TextSpan::new(range)
+        })
+    }
+
+    pub fn with_styled_span(mut self, span: TextSpan) -> TextStyle {
+        self.spans.push(span);
+        self
+    }
+}
+
+// Byte range of every line in s, without the '\n's. Handy for building spans
+// for text whose lines mean different things (a card's title, stats, ...).
+pub fn line_ranges(s: &str) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = 0;
+    for line in s.split('\n') {
+        ranges.push(start..start + line.len());
+        start += line.len() + 1;
+    }
+    ranges
+}
+
+// fn print_to_file(c: char, )
+
+// Blends a coverage bitmap into the canvas with its top-left corner at (x, y).
+// Pixels that land outside the canvas's clip rect are skipped.
+fn stamp_glyph(canvas: &mut Canvas, bitmap: &[u8], glyph_w: usize, x: i64, y: i64, c: Color) {
+    if glyph_w == 0 {
+        return;
+    }
+    let clip = canvas.clip_rect();
+    let (left, right) = (clip.x as i64, clip.right() as i64);
+    let (top, bottom) = (clip.y as i64, clip.bottom() as i64);
+    for (row, line) in bitmap.chunks(glyph_w).enumerate() {
+        let py = y + row as i64;
+        if py < top || py >= bottom {
+            continue;
+        }
+        for (col, &coverage) in line.iter().enumerate() {
+            let px = x + col as i64;
+            if coverage == 0 || px < left || px >= right {
+                //skip adding the background!
+                continue;
+            }
+            let j = py as usize * canvas.width + px as usize;
+            canvas.fb[j] = blend(canvas.fb[j], with_coverage(c, coverage));
+        }
+    }
+}
+
+pub fn render_character(
+    c: char,
+    canvas: &mut Canvas,
+    x: usize,
+    y: usize,
+    size: f32,
+    font: &Font,
+    color: Color,
+) -> (usize, usize) {
+    render_styled_character(c, canvas, x, y, size, font, &TextStyle::new(color))
+}
+
+pub fn render_styled_character(
+    c: char,
+    canvas: &mut Canvas,
+    x: usize,
+    y: usize,
+    size: f32,
+    font: &Font,
+    style: &TextStyle,
+) -> (usize, usize) {
+    let (metrics, bitmap) = font.rasterize(c, size);
+    stamp_styled_glyph(canvas, &bitmap, metrics.width, x as i64, y as i64, style.color, 0, style);
+
+    //return these for use in text
+    (metrics.width, metrics.height)
+}
+
+// Stamps shadow, outline and fill in that order so each layer sits on top of
+// the one before. The glyph is only rasterized once for all three. Faux bold
+// smears every layer embolden pixels to the right.
+#[allow(clippy::too_many_arguments)]
+fn stamp_styled_glyph(
+    canvas: &mut Canvas,
+    bitmap: &[u8],
+    glyph_w: usize,
+    x: i64,
+    y: i64,
+    fill: Color,
+    embolden: i64,
+    style: &TextStyle,
+) {
+    let mut stamp = |x: i64, y: i64, c: Color| {
+        for dx in 0..=embolden {
+            stamp_glyph(canvas, bitmap, glyph_w, x + dx, y, c);
+        }
+    };
+
+    // The bitmap is coverage, so it becomes the alpha of the text color and
+    // anti-aliased edges blend into whatever is underneath.
+    if let Some((shadow_color, (dx, dy))) = style.shadow {
+        stamp(x + dx as i64, y + dy as i64, shadow_color);
+    }
+    if let Some((outline_color, width)) = style.outline {
+        let w = width as i64;
+        for dy in -w..=w {
+            for dx in -w..=w {
+                if (dx, dy) != (0, 0) && dx * dx + dy * dy <= w * w + 1 {
+                    stamp(x + dx, y + dy, outline_color);
+                }
+            }
+        }
+    }
+    stamp(x, y, fill);
+}
+
+pub fn draw_text(
+    canvas: &mut Canvas,
+    s: String,
+    r: Rect,
+    size: f32,
+    font: &Font,
+    style: &TextStyle,
+) {
+    // This one just stops when it runs out of room. For text that sizes itself
+    // to its rect, use draw_layout_text with TextStyle::fit_to_rect.
+
+    let mut x = r.x;
+    let mut y = r.y;
+    let hor_lim = r.x + r.w;
+    let ver_lim = r.y + r.h;
+    let mut avg_space = 0;
+
+    for word in s.split_whitespace() {
+        for c in word.chars() {
+            let (new_w, new_h) = render_styled_character(c, canvas, x, y, size, font, style);
+            avg_space = new_w;
+
+            x += new_w;
+
+            if c == '\n' {
+                y += avg_space;
+            }
+
+            if x >= hor_lim {
+                x = r.x;
+                y += new_h;
+            }
+
+            if y >= ver_lim {
+                //stop drawing - sucks to suck
+                return;
+            }
+        }
+
+        x += avg_space;
+    }
+}
+
+// Everything that affects where glyphs land, so a layout can be reused for as
+// long as none of it changes. Positions are relative to the rect, so moving the
+// text around doesn't invalidate it.
+#[derive(PartialEq, Eq, Hash)]
+pub(crate) struct LayoutKey {
+    fonts: Vec<usize>,
+    fallbacks: Vec<FontId>,
+    text: String,
+    font: FontId,
+    size: u32,
+    w: usize,
+    h: usize,
+    h_align: HAlign,
+    v_align: VAlign,
+    wrap: Wrap,
+    line_spacing: u32,
+    spans: Vec<SpanKey>,
+    fit: Option<(u32, bool)>,
+    markup: bool,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct SpanKey {
+    range: (usize, usize),
+    size: Option<u32>,
+    font: Option<FontId>,
+    color: Option<Color>,
+    bold: bool,
+    icon: Option<Icon>,
+}
+
+fn layout_key(s: &str, r: Rect, fonts: &FontRegistry, font: FontId, size: f32, style: &TextStyle) -> LayoutKey {
+    LayoutKey {
+        fonts: fonts.as_slice().iter().map(Font::file_hash).collect(),
+        fallbacks: fonts.fallbacks().to_vec(),
+        text: s.to_string(),
+        font,
+        size: size.to_bits(),
+        w: r.w,
+        h: r.h,
+        h_align: style.h_align,
+        v_align: style.v_align,
+        wrap: style.wrap,
+        line_spacing: style.line_spacing.to_bits(),
+        spans: style
+            .spans
+            .iter()
+            .map(|span| SpanKey {
+                range: (span.range.start, span.range.end),
+                size: span.size.map(f32::to_bits),
+                font: span.font,
+                color: span.color,
+                bold: span.bold,
+                icon: span.icon,
+            })
+            .collect(),
+        fit: style.fit.map(|fit| (fit.min_size.to_bits(), fit.ellipsis)),
+        markup: style.markup,
+    }
+}
+
+fn cached_layout(
+    cache: &mut GlyphCache,
+    s: &str,
+    r: Rect,
+    fonts: &FontRegistry,
+    font: FontId,
+    size: f32,
+    style: &TextStyle,
+) -> Rc<Vec<PlacedGlyph>> {
+    let key = layout_key(s, r, fonts, font, size, style);
+    cache.layout(key, || {
+        with_markup_parsed(s, style, |s, style| lay_out(s, r, fonts, font, size, style))
+    })
+}
+
+// Calls f with the text as it's actually laid out. With markup on that's the
+// parsed text, and the style gets the markup's spans after its own so they win.
+fn with_markup_parsed<R, F: FnOnce(&str, &TextStyle) -> R>(s: &str, style: &TextStyle, f: F) -> R {
+    if !style.markup {
+        return f(s, style);
+    }
+    let markup = parse_markup(s);
+    let mut style = style.clone();
+    style.spans.extend(markup.spans);
+    f(&markup.text, &style)
+}
+
+fn lay_out(
+    s: &str,
+    r: Rect,
+    fonts: &FontRegistry,
+    font: FontId,
+    size: f32,
+    style: &TextStyle,
+) -> Vec<PlacedGlyph> {
+    match style.fit {
+        Some(fit) => fit_glyphs(s, r, fonts, font, size, style, fit),
+        None => layout_glyphs(s, r, fonts, font, size, style).glyphs,
+    }
+}
+
+// Lays the string out in r and draws it with glyphs from the cache, so an
+// unchanged board doesn't rasterize anything. font and size apply wherever no
+// span overrides them.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_layout_text(
+    canvas: &mut Canvas,
+    cache: &mut GlyphCache,
+    s: &str,
+    r: Rect,
+    fonts: &FontRegistry,
+    font: FontId,
+    size: f32,
+    style: &TextStyle,
+) {
+    let glyphs = cached_layout(cache, s, r, fonts, font, size, style);
+
+    for glyph in glyphs.iter() {
+        let (x, y) = glyph_origin(r, glyph);
+        let bitmap = cache.glyph(fonts.font(glyph.font), glyph.c, glyph.px);
+        let (dx, w, _) = glyph_ink(glyph, &bitmap.metrics);
+        match glyph.icon {
+            Some(icon) => {
+                let coverage = cache.icon(icon, w);
+                stamp_styled_glyph(canvas, &coverage, w, x + dx, y, icon.color(), 0, style);
+            }
+            None => {
+                let fill = glyph.color.unwrap_or(style.color);
+                let glyph_w = bitmap.metrics.width;
+                let embolden = (w - glyph_w) as i64;
+                stamp_styled_glyph(canvas, &bitmap.bitmap, glyph_w, x + dx, y, fill, embolden, style);
+            }
+        }
+    }
+}
+
+// Where a placed glyph actually paints, as an x offset from its origin and a
+// width and height. Bold glyphs are wider than their bitmap, and an icon is a
+// square as tall as its placeholder, centered on it.
+fn glyph_ink(glyph: &PlacedGlyph, metrics: &Metrics) -> (i64, usize, usize) {
+    if glyph.icon.is_some() {
+        let side = metrics.height;
+        return ((metrics.width as i64 - side as i64) / 2, side, side);
+    }
+    let embolden = if glyph.bold { (glyph.px / 20.0).max(1.0) as usize } else { 0 };
+    (0, metrics.width + embolden, metrics.height)
+}
+
+fn glyph_origin(r: Rect, glyph: &PlacedGlyph) -> (i64, i64) {
+    (
+        (r.x as f32 + glyph.x).floor() as i64,
+        (r.y as f32 + glyph.y).floor() as i64,
+    )
+}
+
+// The glyphs draw_layout_text would place in r, after fitting and markup. With
+// fit_to_rect that's where to look for the size it settled on and whether it
+// had to be cut short.
+pub fn layout_text(
+    cache: &mut GlyphCache,
+    s: &str,
+    r: Rect,
+    fonts: &FontRegistry,
+    font: FontId,
+    size: f32,
+    style: &TextStyle,
+) -> Rc<Vec<PlacedGlyph>> {
+    cached_layout(cache, s, r, fonts, font, size, style)
+}
+
+// The screen area draw_layout_text would paint, outline and shadow included.
+// Glyphs aren't confined to r, so this is what has to be repainted when the
+// text changes.
+pub fn layout_text_bounds(
+    cache: &mut GlyphCache,
+    s: &str,
+    r: Rect,
+    fonts: &FontRegistry,
+    font: FontId,
+    size: f32,
+    style: &TextStyle,
+) -> Rect {
+    let glyphs = cached_layout(cache, s, r, fonts, font, size, style);
+
+    let (mut left, mut top, mut right, mut bottom) = (i64::MAX, i64::MAX, i64::MIN, i64::MIN);
+    for glyph in glyphs.iter() {
+        let metrics = cache.glyph(fonts.font(glyph.font), glyph.c, glyph.px).metrics;
+        if metrics.width == 0 || metrics.height == 0 {
+            continue;
+        }
+        let (x, y) = glyph_origin(r, glyph);
+        let (dx, w, h) = glyph_ink(glyph, &metrics);
+        left = left.min(x + dx);
+        top = top.min(y);
+        right = right.max(x + dx + w as i64);
+        bottom = bottom.max(y + h as i64);
+    }
+    if left > right {
+        return Rect::new(r.x, r.y, 0, 0);
+    }
+
+    if let Some((_, width)) = style.outline {
+        let w = width as i64;
+        left -= w;
+        top -= w;
+        right += w;
+        bottom += w;
+    }
+    if let Some((_, (dx, dy))) = style.shadow {
+        left = left.min(left + dx as i64);
+        top = top.min(top + dy as i64);
+        right = right.max(right + dx as i64);
+        bottom = bottom.max(bottom + dy as i64);
+    }
+    let (left, top) = (left.max(0), top.max(0));
+    Rect::new(
+        left as usize,
+        top as usize,
+        (right - left).max(0) as usize,
+        (bottom - top).max(0) as usize,
+    )
+}
+
+// What a run carries through fontdue's layout onto each of its glyphs.
+#[derive(Copy, Clone, Default)]
+struct RunData {
+    color: Option<Color>,
+    bold: bool,
+    icon: Option<Icon>,
+}
+
+// How much room a string takes up, from measure_text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextMetrics {
+    // The widest line, from the first glyph's pen position to the last one's
+    // advance. Text wrapped to at least this width keeps its line breaks.
+    pub width: f32,
+    // Every line's height plus the line spacing between them.
+    pub height: f32,
+    pub lines: usize,
+    // Relative to the top-left corner the text would be drawn from.
+    pub glyphs: Vec<PlacedGlyph>,
+}
+
+impl TextMetrics {
+    // The smallest whole-pixel rect at (x, y) that holds the text.
+    pub fn rect_at(&self, x: usize, y: usize) -> Rect {
+        Rect::new(x, y, self.width.ceil() as usize, self.height.ceil() as usize)
+    }
+
+    // The same, centered on (cx, cy).
+    pub fn rect_centered_on(&self, cx: usize, cy: usize) -> Rect {
+        let r = self.rect_at(0, 0);
+        Rect::new(cx.saturating_sub(r.w / 2), cy.saturating_sub(r.h / 2), r.w, r.h)
+    }
+}
+
+// Lays the string out like draw_layout_text would, wrapped at max_width (or not
+// at all for None), and reports how big it came out. Spans, markup and line
+// spacing count; alignment and fit_to_rect don't, since there's no rect yet.
+pub fn measure_text(
+    fonts: &FontRegistry,
+    s: &str,
+    font: FontId,
+    size: f32,
+    max_width: Option<usize>,
+    style: &TextStyle,
+) -> TextMetrics {
+    let style = TextStyle {
+        h_align: HAlign::Left,
+        v_align: VAlign::Top,
+        wrap: if max_width.is_some() { style.wrap } else { Wrap::Off },
+        fit: None,
+        ..style.clone()
+    };
+    let r = Rect::new(0, 0, max_width.unwrap_or(0), 0);
+    let laid = with_markup_parsed(s, &style, |s, style| layout_glyphs(s, r, fonts, font, size, style));
+
+    // fontdue puts every glyph at its pen position plus the glyph's xmin, and
+    // advances the pen by whole pixels. Control characters ('\n') take no room.
+    let pen = |glyph: &PlacedGlyph| {
+        let metrics = fonts.font(glyph.font).metrics(glyph.c, glyph.px);
+        (glyph.x - metrics.xmin as f32, metrics.advance_width.ceil())
+    };
+    let mut width: f32 = 0.0;
+    for line in laid.lines.iter() {
+        let mut glyphs = laid.glyphs[line.clone()].iter().filter(|g| !g.c.is_control());
+        if let Some(first) = glyphs.next() {
+            let (left, _) = pen(first);
+            let (x, advance) = pen(glyphs.next_back().unwrap_or(first));
+            width = width.max(x + advance - left);
+        }
+    }
+    TextMetrics {
+        width,
+        height: laid.height,
+        lines: laid.lines.len(),
+        glyphs: laid.glyphs,
+    }
+}
+
+// Splits s at span boundaries into runs of (text, size, font, the rest). Every
+// size is multiplied by scale. A span boundary inside a multibyte character is
+// moved back to the start of that character.
+fn styled_runs<'s>(
+    s: &'s str,
+    font: FontId,
+    size: f32,
+    scale: f32,
+    spans: &[TextSpan],
+) -> Vec<(&'s str, f32, FontId, RunData)> {
+    let ranges: Vec<(usize, usize)> = spans
+        .iter()
+        .map(|span| (floor_char_boundary(s, span.range.start), floor_char_boundary(s, span.range.end)))
+        .collect();
+    let mut cuts = vec![0, s.len()];
+    for &(start, end) in &ranges {
+        cuts.push(start);
+        cuts.push(end);
+    }
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    cuts.windows(2)
+        .map(|w| {
+            let (start, end) = (w[0], w[1]);
+            let text = &s[start..end];
+            let (mut run_size, mut run_font, mut data) = (size, font, RunData::default());
+            let covering = spans.iter().zip(&ranges).filter(|(_, r)| r.0 <= start && end <= r.1);
+            for (span, _) in covering {
+                run_size = span.size.unwrap_or(run_size);
+                run_font = span.font.unwrap_or(run_font);
+                data.color = span.color.or(data.color);
+                data.bold |= span.bold;
+                data.icon = span.icon.or(data.icon);
+            }
+            (text, run_size * scale, run_font, data)
+        })
+        .collect()
+}
+
+// The largest index no greater than i (or s.len()) that starts a character.
+fn floor_char_boundary(s: &str, i: usize) -> usize {
+    let mut i = i.min(s.len());
+    while !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+// Splits a run wherever its font is missing characters, so each piece is drawn
+// with the font fonts.font_for picks for it.
+fn fallback_runs<'s>(fonts: &FontRegistry, text: &'s str, font: FontId) -> Vec<(&'s str, FontId)> {
+    let mut runs = vec![];
+    let mut start = 0;
+    let mut current = font;
+    for (i, c) in text.char_indices() {
+        let wanted = fonts.font_for(font, c);
+        if wanted != current {
+            if i > start {
+                runs.push((&text[start..i], current));
+            }
+            start = i;
+            current = wanted;
+        }
+    }
+    if start < text.len() {
+        runs.push((&text[start..], current));
+    }
+    runs
+}
+
+// Glyphs positioned relative to the rect's corner, plus the size of the block
+// of text they make up.
+struct LaidOut {
+    glyphs: Vec<PlacedGlyph>,
+    width: f32,
+    height: f32,
+    // which glyphs are on each line
+    lines: Vec<Range<usize>>,
+}
+
+impl LaidOut {
+    fn fits(&self, r: Rect) -> bool {
+        self.width <= r.w as f32 && self.height <= r.h as f32
+    }
+}
+
+fn layout_glyphs(
+    s: &str,
+    r: Rect,
+    fonts: &FontRegistry,
+    font: FontId,
+    size: f32,
+    style: &TextStyle,
+) -> LaidOut {
+    layout_scaled(s, r, fonts, font, size, 1.0, style)
+}
+
+// Runs fontdue's layout with every size multiplied by scale. fontdue handles
+// wrapping and, when wrapping, horizontal alignment; line spacing and vertical
+// alignment are applied here so they work the same whatever the wrap mode.
+#[allow(clippy::too_many_arguments)]
+fn layout_scaled(
+    s: &str,
+    r: Rect,
+    fonts: &FontRegistry,
+    font: FontId,
+    size: f32,
+    scale: f32,
+    style: &TextStyle,
+) -> LaidOut {
+    let mut layout = Layout::<RunData>::new(CoordinateSystem::PositiveYDown);
+
+    let wrapping = style.wrap != Wrap::Off;
+    let lay_settings = LayoutSettings {
+        max_width: if wrapping { Some(r.w as f32) } else { None },
+        horizontal_align: match (wrapping, style.h_align) {
+            (true, HAlign::Center) => HorizontalAlign::Center,
+            (true, HAlign::Right) => HorizontalAlign::Right,
+            _ => HorizontalAlign::Left,
+        },
+        vertical_align: VerticalAlign::Top,
+        wrap_style: match style.wrap {
+            Wrap::Letter => WrapStyle::Letter,
+            _ => WrapStyle::Word,
+        },
+        wrap_hard_breaks: true,
+        ..LayoutSettings::default()
+    };
+
+    layout.reset(&lay_settings);
+
+    for (text, run_size, run_font, data) in styled_runs(s, font, size, scale, &style.spans) {
+        for (text, run_font) in fallback_runs(fonts, text, run_font) {
+            let run = FontdueStyle::with_user_data(text, run_size, run_font.index(), data);
+            layout.append(fonts.as_slice(), &run);
+        }
+    }
+
+    let glyphs = layout.glyphs();
+    let lines = match layout.lines() {
+        Some(lines) => lines,
+        None => {
+            return LaidOut {
+                glyphs: vec![],
+                width: 0.0,
+                height: 0.0,
+                lines: vec![],
+            }
+        }
+    };
+
+    let mut placed = Vec::with_capacity(glyphs.len());
+    let mut line_ranges = Vec::with_capacity(lines.len());
+    let mut extra_y = 0.0;
+    for line in lines.iter() {
+        let line_glyphs = glyphs.get(line.glyph_start..=line.glyph_end).unwrap_or(&[]);
+        let mut shift_x = 0.0;
+        if !wrapping && style.h_align != HAlign::Left {
+            let left = line_glyphs.iter().map(|g| g.x).fold(f32::MAX, f32::min);
+            let right = line_glyphs.iter().map(|g| g.x + g.width as f32).fold(f32::MIN, f32::max);
+            let slack = r.w as f32 - (right - left);
+            shift_x = if style.h_align == HAlign::Center { slack / 2.0 } else { slack } - left;
+        }
+        let first = placed.len();
+        for glyph in line_glyphs {
+            placed.push(PlacedGlyph {
+                c: glyph.parent,
+                x: glyph.x + shift_x,
+                y: glyph.y + extra_y,
+                px: glyph.key.px,
+                font: FontId(glyph.font_index),
+                color: glyph.user_data.color,
+                bold: glyph.user_data.bold,
+                icon: glyph.user_data.icon,
+            });
+        }
+        line_ranges.push(first..placed.len());
+        extra_y += (style.line_spacing - 1.0) * line.max_new_line_size;
+    }
+
+    // the last line's spacing is below the text, so it doesn't count
+    let last_gap = (style.line_spacing - 1.0) * lines.last().map_or(0.0, |l| l.max_new_line_size);
+    let height = layout.height() + extra_y - last_gap;
+    let slack = r.h as f32 - height;
+    let shift_y = match style.v_align {
+        VAlign::Top => 0.0,
+        VAlign::Middle => slack / 2.0,
+        VAlign::Bottom => slack,
+    };
+    for glyph in placed.iter_mut() {
+        glyph.y += shift_y;
+    }
+
+    let inked = glyphs.iter().filter(|g| g.width > 0);
+    let left = inked.clone().map(|g| g.x).fold(f32::MAX, f32::min);
+    let right = inked.map(|g| g.x + g.width as f32).fold(f32::MIN, f32::max);
+    LaidOut {
+        glyphs: placed,
+        width: (right - left).max(0.0),
+        height,
+        lines: line_ranges,
+    }
+}
+
+// The largest whole pixel size between fit.min_size and size whose layout fits
+// in r. Spans scale along with the base size so titles stay bigger than body
+// text. If nothing fits, the text is cut short at min_size.
+#[allow(clippy::too_many_arguments)]
+fn fit_glyphs(
+    s: &str,
+    r: Rect,
+    fonts: &FontRegistry,
+    font: FontId,
+    size: f32,
+    style: &TextStyle,
+    fit: TextFit,
+) -> Vec<PlacedGlyph> {
+    let at = |px: f32, text: &str| layout_scaled(text, r, fonts, font, size, px / size, style);
+
+    let full = at(size, s);
+    if full.fits(r) || fit.min_size >= size {
+        return full.glyphs;
+    }
+
+    // binary search for the biggest size that fits; lo always fits (or is the minimum)
+    let (mut lo, mut hi) = (fit.min_size.ceil() as u32, size.floor() as u32);
+    let smallest = at(lo as f32, s);
+    if !smallest.fits(r) {
+        return if fit.ellipsis {
+            // not every font (or fallback) has a real ellipsis character
+            let has_ellipsis = fonts.has_char(fonts.font_for(font, '\u{2026}'), '\u{2026}');
+            let ellipsis = if has_ellipsis { "\u{2026}" } else { "..." };
+            ellipsize(s, r, ellipsis, |text, cut| {
+                // spans past the cut would land in the middle of the ellipsis
+                let mut style = style.clone();
+                for span in style.spans.iter_mut() {
+                    span.range = span.range.start.min(cut)..span.range.end.min(cut);
+                }
+                layout_scaled(text, r, fonts, font, size, lo as f32 / size, &style)
+            })
+        } else {
+            smallest.glyphs
+        };
+    }
+    while lo < hi {
+        let mid = hi - (hi - lo) / 2;
+        if at(mid as f32, s).fits(r) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    at(lo as f32, s).glyphs
+}
+
+// The longest prefix of s that still fits with an ellipsis on the end.
+// layout_at gets the shortened text and the byte length of the kept prefix.
+fn ellipsize<F: Fn(&str, usize) -> LaidOut>(s: &str, r: Rect, ellipsis: &str, layout_at: F) -> Vec<PlacedGlyph> {
+    let with_ellipsis = |chars: usize| {
+        let end = s.char_indices().nth(chars).map_or(s.len(), |(i, _)| i);
+        let kept = s[..end].trim_end();
+        layout_at(&format!("{}{}", kept, ellipsis), kept.len())
+    };
+
+    let (mut lo, mut hi) = (0, s.chars().count());
+    while lo < hi {
+        let mid = hi - (hi - lo) / 2;
+        if with_ellipsis(mid).fits(r) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    with_ellipsis(lo).glyphs
+}
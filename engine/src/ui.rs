@@ -0,0 +1,313 @@
+// Immediate-mode widgets. Call them every frame with where they go; each one
+// puts its drawables on the Ui layer and says what the mouse did to it this
+// frame. There's no widget tree to keep in sync with the game, whatever got
+// called this frame is what's on screen:
+//
+//   ui.begin(&mut state);
+//   ui.panel(&mut state, menu_rect);
+//   if ui.button(&mut state, end_turn_rect, "End turn").clicked {
+//       turn += 1;
+//   }
+//   ui.slider(&mut state, volume_rect, &mut volume, 0.0..=1.0);
+//
+// A widget is known by its rect, so one that moves while it's held loses the
+// press. The drawables are rebuilt every frame, but draw() only repaints the
+// ones that actually look different.
+
+use crate::{
+    circle_rect, Color, Drawable, DrawableId, FontId, HAlign, Layer, MouseButton, Rect,
+    ShapeStyle, State, TextStyle, VAlign, Wrap,
+};
+use std::ops::RangeInclusive;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UiStyle {
+    pub panel: Color,
+    pub border: Color,
+    // widget backgrounds, by state
+    pub normal: Color,
+    pub hovered: Color,
+    pub pressed: Color,
+    pub disabled: Color,
+    pub text: Color,
+    pub disabled_text: Color,
+    // checkbox ticks, slider fills, progress bars
+    pub accent: Color,
+    pub track: Color,
+    pub font: FontId,
+    pub font_size: f32,
+    pub corner_radius: usize,
+}
+
+impl Default for UiStyle {
+    fn default() -> UiStyle {
+        UiStyle {
+            panel: (40, 44, 52, 230),
+            border: (20, 20, 24, 255),
+            normal: (70, 80, 100, 255),
+            hovered: (90, 104, 130, 255),
+            pressed: (50, 58, 74, 255),
+            disabled: (64, 64, 64, 255),
+            text: (255, 255, 255, 255),
+            disabled_text: (150, 150, 150, 255),
+            accent: (255, 215, 0, 255),
+            track: (30, 32, 38, 255),
+            font: FontId::CARD_BODY,
+            font_size: 28.0,
+            corner_radius: 8,
+        }
+    }
+}
+
+// What the mouse did to a widget this frame.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Response {
+    pub hovered: bool,
+    // the left button went down on it and is still down, wherever it is now
+    pub pressed: bool,
+    // pressed and let go on it
+    pub clicked: bool,
+    // the value it edits changed
+    pub changed: bool,
+}
+
+pub struct Ui {
+    pub style: UiStyle,
+    enabled: bool,
+    // the widget the left button went down on
+    active: Option<Rect>,
+    mouse_over: bool,
+    drawn: Vec<DrawableId>,
+}
+
+impl Default for Ui {
+    fn default() -> Ui {
+        Ui::new()
+    }
+}
+
+impl Ui {
+    pub fn new() -> Ui {
+        Ui::with_style(UiStyle::default())
+    }
+
+    pub fn with_style(style: UiStyle) -> Ui {
+        Ui {
+            style,
+            enabled: true,
+            active: None,
+            mouse_over: false,
+            drawn: vec![],
+        }
+    }
+
+    // Clears last frame's widgets. Call it once a frame before any widget.
+    pub fn begin(&mut self, state: &mut State) {
+        for id in self.drawn.drain(..) {
+            state.drawables.remove(id);
+        }
+        self.mouse_over = false;
+        // kept through the release frame so the widget sees the click, and
+        // dropped on the next press, which the widgets pick up for themselves
+        let left = MouseButton::Left;
+        let held = state.mouse_down(left) && !state.mouse_just_pressed(left);
+        if !held && !state.mouse_just_released(left) {
+            self.active = None;
+        }
+    }
+
+    // Widgets after this are greyed out and ignore the mouse until it's set
+    // back to true.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // Whether the mouse is the UI's this frame: over a widget drawn so far, or
+    // held down on one. Games check it after their widgets and leave clicks
+    // alone when it's true, so a button over the board doesn't also pick up
+    // the card under it.
+    pub fn wants_mouse(&self) -> bool {
+        self.mouse_over || self.active.is_some()
+    }
+
+    pub fn label(&mut self, state: &mut State, rect: Rect, text: &str) {
+        let color = self.text_color();
+        let style = TextStyle::new(color).aligned(HAlign::Left, VAlign::Middle);
+        self.text(state, rect, text, style);
+    }
+
+    pub fn panel(&mut self, state: &mut State, rect: Rect) {
+        let (panel, border) = (self.style.panel, self.style.border);
+        self.rounded(state, rect, ShapeStyle::filled(panel));
+        self.rounded(state, rect, ShapeStyle::outlined(border, 2));
+        // panels catch the mouse too, so clicks don't go through them
+        self.interact(state, rect);
+    }
+
+    pub fn button(&mut self, state: &mut State, rect: Rect, text: &str) -> Response {
+        let response = self.interact(state, rect);
+        let fill = self.fill(&response);
+        self.rounded(state, rect, ShapeStyle::filled(fill));
+        self.rounded(state, rect, ShapeStyle::outlined(self.style.border, 2));
+        let style = TextStyle::new(self.text_color())
+            .aligned(HAlign::Center, VAlign::Middle)
+            .with_wrap(Wrap::Off);
+        self.text(state, rect, text, style);
+        response
+    }
+
+    // A box with the label to its right. Clicking anywhere on it flips value.
+    pub fn checkbox(
+        &mut self,
+        state: &mut State,
+        rect: Rect,
+        text: &str,
+        value: &mut bool,
+    ) -> Response {
+        let mut response = self.interact(state, rect);
+        if response.clicked {
+            *value = !*value;
+            response.changed = true;
+        }
+
+        let side = rect.h;
+        let tick_box = Rect::new(rect.x, rect.y, side, side);
+        let fill = self.fill(&response);
+        self.rounded(state, tick_box, ShapeStyle::filled(fill));
+        self.rounded(state, tick_box, ShapeStyle::outlined(self.style.border, 2));
+        if *value {
+            let inset = side / 4;
+            let tick_side = side - 2 * inset;
+            let tick = Rect::new(rect.x + inset, rect.y + inset, tick_side, tick_side);
+            let accent = self.accent();
+            self.rounded(state, tick, ShapeStyle::filled(accent).anti_aliased());
+        }
+
+        let gap = side / 3;
+        let label_w = rect.w.saturating_sub(side + gap);
+        let label = Rect::new(rect.x + side + gap, rect.y, label_w, rect.h);
+        self.label(state, label, text);
+        response
+    }
+
+    // Drag anywhere along it to set value; it follows the mouse even off the
+    // ends until the button comes up.
+    pub fn slider(
+        &mut self,
+        state: &mut State,
+        rect: Rect,
+        value: &mut f32,
+        range: RangeInclusive<f32>,
+    ) -> Response {
+        let mut response = self.interact(state, rect);
+        let (min, max) = (*range.start(), *range.end());
+        // no bigger than the rect is wide, and its center stays inside the rect
+        let radius = rect.w.min(rect.h) / 2;
+        let (left, travel) = (rect.x + radius, rect.w.saturating_sub(2 * radius));
+
+        if response.pressed {
+            let t = (state.mouse_coords.0 as f32 - left as f32) / travel.max(1) as f32;
+            let new = min + t.clamp(0.0, 1.0) * (max - min);
+            if new != *value {
+                *value = new;
+                response.changed = true;
+            }
+        }
+
+        let t = if max > min { ((*value - min) / (max - min)).clamp(0.0, 1.0) } else { 0.0 };
+        let knob_x = left + (t * travel as f32).round() as usize;
+        let track_h = (rect.h / 3).max(1);
+        // at least a pixel high, even when rect isn't
+        let track_y = rect.y + rect.h.saturating_sub(track_h) / 2;
+        let track = Rect::new(rect.x, track_y, rect.w, track_h);
+        let filled = Rect::new(track.x, track.y, knob_x - rect.x, track.h);
+        let accent = self.accent();
+        self.rounded(state, track, ShapeStyle::filled(self.style.track));
+        self.rounded(state, filled, ShapeStyle::filled(accent));
+
+        let knob = circle_rect((knob_x, rect.y + rect.h / 2), radius);
+        let fill = ShapeStyle::filled(self.fill(&response)).anti_aliased();
+        let border = ShapeStyle::outlined(self.style.border, 2).anti_aliased();
+        self.add(state, Drawable::Ellipse(knob, fill, None));
+        self.add(state, Drawable::Ellipse(knob, border, None));
+        response
+    }
+
+    // fraction is clamped to 0..=1.
+    pub fn progress_bar(&mut self, state: &mut State, rect: Rect, fraction: f32) {
+        let width = (rect.w as f32 * fraction.clamp(0.0, 1.0)).round() as usize;
+        let accent = self.accent();
+        self.rounded(state, rect, ShapeStyle::filled(self.style.track));
+        self.rounded(state, Rect::new(rect.x, rect.y, width, rect.h), ShapeStyle::filled(accent));
+        self.rounded(state, rect, ShapeStyle::outlined(self.style.border, 2));
+    }
+
+    fn interact(&mut self, state: &State, rect: Rect) -> Response {
+        let (x, y) = state.mouse_coords;
+        let over = rect.contains_point(x, y);
+        self.mouse_over |= over;
+        if !self.enabled {
+            return Response::default();
+        }
+        if over && state.mouse_just_pressed(MouseButton::Left) {
+            self.active = Some(rect);
+        }
+        let active = self.active == Some(rect);
+        Response {
+            hovered: over,
+            pressed: active && state.mouse_down(MouseButton::Left),
+            clicked: active && over && state.mouse_just_released(MouseButton::Left),
+            changed: false,
+        }
+    }
+
+    // A widget's background for its state. Held but dragged off, it only
+    // looks hovered, since letting go there won't click it.
+    fn fill(&self, response: &Response) -> Color {
+        if !self.enabled {
+            self.style.disabled
+        } else if response.pressed && response.hovered {
+            self.style.pressed
+        } else if response.hovered || response.pressed {
+            self.style.hovered
+        } else {
+            self.style.normal
+        }
+    }
+
+    fn accent(&self) -> Color {
+        if self.enabled {
+            self.style.accent
+        } else {
+            self.style.disabled_text
+        }
+    }
+
+    fn text_color(&self) -> Color {
+        if self.enabled {
+            self.style.text
+        } else {
+            self.style.disabled_text
+        }
+    }
+
+    fn text(&mut self, state: &mut State, rect: Rect, text: &str, style: TextStyle) {
+        let (font, size) = (self.style.font, self.style.font_size);
+        self.add(state, Drawable::Text(rect, text.to_string(), font, size, style));
+    }
+
+    fn rounded(&mut self, state: &mut State, rect: Rect, style: ShapeStyle) {
+        // a radius bigger than half the rect would overlap itself
+        let radius = self.style.corner_radius.min(rect.w / 2).min(rect.h / 2);
+        self.add(state, Drawable::RoundedRect(rect, radius, style, None));
+    }
+
+    fn add(&mut self, state: &mut State, drawable: Drawable) {
+        let id = state.drawables.insert(drawable.on_layer(Layer::Ui, 0));
+        self.drawn.push(id);
+    }
+}
@@ -0,0 +1,42 @@
+use engine::{draw, setup_headless_with, Drawable, EngineConfig, Rect};
+
+#[test]
+fn resolution_parses_and_rejects_empty_sizes() {
+    assert_eq!(EngineConfig::parse("1280x720"), Some(EngineConfig::new(1280, 720)));
+    assert_eq!(EngineConfig::parse(" 800 x 600 "), Some(EngineConfig::new(800, 600)));
+    for bad in ["0x0", "0x720", "1280x0", "1280", "x720", "wide x tall", ""] {
+        assert_eq!(EngineConfig::parse(bad), None, "{:?}", bad);
+    }
+
+    // the only test here touching the environment, so nothing races it
+    // rejected values fall back to the default (with a warning on stdout)
+    for bad in ["0x0", "1280*720"] {
+        std::env::set_var("ENGINE_RESOLUTION", bad);
+        assert_eq!(EngineConfig::from_env(), EngineConfig::default());
+    }
+    std::env::set_var("ENGINE_RESOLUTION", "1280x720");
+    assert_eq!(EngineConfig::from_env(), EngineConfig::new(1280, 720));
+    std::env::remove_var("ENGINE_RESOLUTION");
+}
+
+#[test]
+fn smaller_framebuffer_clips_at_its_edges() {
+    let mut state = setup_headless_with(EngineConfig::new(1280, 720));
+    assert_eq!(state.screen_size(), (1280, 720));
+    assert_eq!(state.fb2d.len(), 1280 * 720);
+
+    // hangs off the bottom right corner, where a 1920x1080 buffer would still have room
+    let red = (255, 0, 0, 255);
+    state.bg_color = (0, 0, 0, 255);
+    state.drawables.insert(Drawable::Rectangle(Rect::new(1270, 710, 100, 100), red, None));
+    draw(&mut state);
+
+    let at = |x: usize, y: usize| state.fb2d[y * 1280 + x];
+    assert_eq!(at(1279, 719), red);
+    assert_eq!(at(1270, 710), red);
+    assert_eq!(at(1269, 719), (0, 0, 0, 255));
+    assert_eq!(at(1279, 709), (0, 0, 0, 255));
+    // the part past the right edge didn't wrap round onto the next row
+    assert_eq!(at(0, 711), (0, 0, 0, 255));
+    assert_eq!(state.fb2d.iter().filter(|&&p| p == red).count(), 10 * 10);
+}
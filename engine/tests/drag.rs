@@ -0,0 +1,129 @@
+use engine::{
+    begin_frame, setup_headless, DragDrop, DragEvent, DraggableSnapType, Drawable, DrawableId,
+    MouseButton, Rect, State,
+};
+
+// A card at (100, 100), a slot it can go in at (600, 100) and a deck frame
+// at (1000, 100) that it can't.
+fn table(state: &mut State) -> (DrawableId, DrawableId, DrawableId) {
+    let rect = |x| Rect::new(x, 100, 200, 300);
+    let snap = Some(DraggableSnapType::Card(false, true));
+    let slot = state.drawables.insert(Drawable::RectOutlined(rect(600), (255, 0, 0, 255), snap));
+    let deck = state.drawables.insert(Drawable::RectOutlined(rect(1000), (0, 0, 0, 255), snap));
+    let card = state.drawables.insert(Drawable::Rectangle(
+        rect(100),
+        (255, 255, 255, 255),
+        Some(DraggableSnapType::Card(true, false)),
+    ));
+    (card, slot, deck)
+}
+
+// One frame: move the mouse, set the left button, run the drag.
+fn frame(
+    state: &mut State,
+    drag: &mut DragDrop<&'static str>,
+    mouse: (usize, usize),
+    down: bool,
+    slot: DrawableId,
+) -> Vec<DragEvent<&'static str>> {
+    begin_frame(state);
+    state.mouse_coords = mouse;
+    state.set_mouse_button(MouseButton::Left, down);
+    drag.update(state, |_, _, target, _| target == slot)
+}
+
+#[test]
+fn accepted_drop_snaps_onto_the_target() {
+    let mut state = setup_headless();
+    let (card, slot, _) = table(&mut state);
+    let mut drag = DragDrop::new();
+    drag.set_payload(card, "knight");
+
+    let events = frame(&mut state, &mut drag, (150, 150), true, slot);
+    assert_eq!(events, vec![DragEvent::Start(card, "knight")]);
+    assert_eq!(state.drag_item_id, Some(card));
+
+    let events = frame(&mut state, &mut drag, (650, 160), true, slot);
+    assert_eq!(events, vec![DragEvent::Over(card, Some(slot), "knight")]);
+    assert_eq!(state.drawables[card].get_coords(), (600, 110));
+    assert_eq!(drag.over(), Some(slot));
+
+    // nothing new while it stays over the same slot
+    assert!(frame(&mut state, &mut drag, (660, 160), true, slot).is_empty());
+
+    let events = frame(&mut state, &mut drag, (660, 160), false, slot);
+    assert_eq!(events, vec![DragEvent::Drop(card, slot, "knight")]);
+    assert_eq!(state.drawables[card].get_coords(), (600, 100));
+    assert_eq!(drag.dragging(), None);
+    assert_eq!(state.drag_item_id, None);
+}
+
+#[test]
+fn refused_drop_goes_back_to_the_start() {
+    let mut state = setup_headless();
+    let (card, slot, deck) = table(&mut state);
+    let mut drag = DragDrop::new();
+    drag.set_payload(card, "knight");
+
+    frame(&mut state, &mut drag, (150, 150), true, slot);
+    frame(&mut state, &mut drag, (1050, 150), true, slot);
+    let events = frame(&mut state, &mut drag, (1050, 150), false, slot);
+    assert_eq!(events, vec![DragEvent::Cancel(card, "knight")]);
+    assert_eq!(state.drawables[card].get_coords(), (100, 100));
+    assert!(drag.payload(deck).is_none());
+
+    // let go over nothing at all
+    frame(&mut state, &mut drag, (150, 150), true, slot);
+    let events = frame(&mut state, &mut drag, (150, 900), false, slot);
+    assert_eq!(events, vec![DragEvent::Cancel(card, "knight")]);
+    assert_eq!(state.drawables[card].get_coords(), (100, 100));
+
+    // without a payload it can't be picked up at all
+    drag.remove_payload(card);
+    assert!(frame(&mut state, &mut drag, (150, 150), true, slot).is_empty());
+    assert_eq!(drag.dragging(), None);
+}
+
+#[test]
+fn the_slot_it_came_from_counts_once_the_pointer_leaves_it() {
+    let mut state = setup_headless();
+    let (card, slot, _) = table(&mut state);
+    let mut drag = DragDrop::new();
+    drag.set_payload(card, "knight");
+    frame(&mut state, &mut drag, (150, 150), true, slot);
+    frame(&mut state, &mut drag, (650, 150), true, slot);
+    frame(&mut state, &mut drag, (650, 150), false, slot);
+    assert_eq!(state.drawables[card].get_coords(), (600, 100));
+
+    // picking it back up isn't over its own slot, and letting go there
+    // doesn't ask whether it can go in it (it would say yes), it just goes back
+    let events = frame(&mut state, &mut drag, (650, 160), true, slot);
+    assert_eq!(events, vec![DragEvent::Start(card, "knight")]);
+    assert!(frame(&mut state, &mut drag, (660, 170), true, slot).is_empty());
+    assert_eq!(drag.over(), None);
+    let events = frame(&mut state, &mut drag, (660, 170), false, slot);
+    assert_eq!(events, vec![DragEvent::Cancel(card, "knight")]);
+    assert_eq!(state.drawables[card].get_coords(), (600, 100));
+
+    // once it's been off the slot, coming back counts
+    frame(&mut state, &mut drag, (650, 160), true, slot);
+    assert!(frame(&mut state, &mut drag, (150, 160), true, slot).is_empty());
+    let events = frame(&mut state, &mut drag, (650, 160), true, slot);
+    assert_eq!(events, vec![DragEvent::Over(card, Some(slot), "knight")]);
+    let events = frame(&mut state, &mut drag, (650, 160), false, slot);
+    assert_eq!(events, vec![DragEvent::Drop(card, slot, "knight")]);
+}
+
+#[test]
+fn removing_the_dragged_drawable_cancels() {
+    let mut state = setup_headless();
+    let (card, slot, _) = table(&mut state);
+    let mut drag = DragDrop::new();
+    drag.set_payload(card, "knight");
+
+    frame(&mut state, &mut drag, (150, 150), true, slot);
+    state.drawables.remove(card);
+    let events = frame(&mut state, &mut drag, (650, 150), true, slot);
+    assert_eq!(events, vec![DragEvent::Cancel(card, "knight")]);
+    assert_eq!(state.drag_item_id, None);
+}
@@ -0,0 +1,673 @@
+use engine::golden::{assert_golden, compare_images, render_offscreen, Frame};
+use engine::{
+    arrow, card_text_style, circle_rect, draw, layout_text, layout_text_bounds, line_ranges,
+    measure_text, setup_headless, Color, DraggableSnapType, Drawable, FontId, GlyphCache, HAlign,
+    Image, Rect, ShapeStyle, Sprite, TextSpan, TextStyle, VAlign, Wrap,
+};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+const BOARD: Color = (91, 99, 112, 255);
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+#[test]
+fn rectangle() {
+    let fb = render_offscreen(
+        vec![
+            Drawable::Rectangle(Rect::new(10, 10, 200, 100), (255, 0, 0, 255), None),
+            Drawable::Rectangle(Rect::new(150, 60, 200, 100), (0, 0, 255, 255), None),
+        ],
+        BOARD,
+    );
+    assert_golden(golden_dir(), "rectangle", &fb, 0);
+}
+
+#[test]
+fn rect_outlined() {
+    let fb = render_offscreen(
+        vec![Drawable::RectOutlined(
+            Rect::new(40, 40, 213, 180),
+            (255, 255, 255, 255),
+            Some(DraggableSnapType::Card(false, true)),
+        )],
+        BOARD,
+    );
+    assert_golden(golden_dir(), "rect_outlined", &fb, 0);
+}
+
+#[test]
+fn text() {
+    let fb = render_offscreen(
+        vec![Drawable::Text(
+            Rect::new(100, 100, 600, 200),
+            "Player 1 has 5 mana".to_string(),
+            FontId::GAME_TITLE,
+            40.0,
+            TextStyle::default(),
+        )],
+        BOARD,
+    );
+    assert_golden(golden_dir(), "text", &fb, 2);
+}
+
+#[test]
+fn styled_text() {
+    let fb = render_offscreen(
+        vec![
+            Drawable::Text(
+                Rect::new(100, 100, 600, 100),
+                "Outlined".to_string(),
+                FontId::GAME_TITLE,
+                60.0,
+                TextStyle::new((255, 215, 0, 255)).with_outline((0, 0, 0, 255), 2),
+            ),
+            Drawable::Text(
+                Rect::new(100, 250, 600, 100),
+                "Shadowed".to_string(),
+                FontId::GAME_TITLE,
+                60.0,
+                TextStyle::new((255, 255, 255, 255)).with_shadow((0, 0, 0, 160), (3, 3)),
+            ),
+        ],
+        BOARD,
+    );
+    assert_golden(golden_dir(), "styled_text", &fb, 2);
+
+    let at = |x: usize, y: usize| fb.pixels[y * fb.width + x];
+    let pixels = |rows: std::ops::Range<usize>| rows.flat_map(|y| (90..800).map(move |x| (x, y)));
+    let (gold, white) = ((255, 215, 0, 255), (255, 255, 255, 255));
+    // black at 160 alpha over the board
+    let shadow = (34, 37, 42, 255);
+
+    // the fill is there, and the outline keeps it off the board on every side
+    let filled: Vec<_> = pixels(80..230).filter(|&(x, y)| at(x, y) == gold).collect();
+    assert!(!filled.is_empty());
+    for (x, y) in filled {
+        for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            assert_ne!(at(nx, ny), BOARD, "gold at {:?} touches the board", (x, y));
+        }
+    }
+
+    // every solid white pixel has shadow (or more text) 3 down and 3 right of it
+    let solid: Vec<_> = pixels(230..380).filter(|&(x, y)| at(x, y) == white).collect();
+    assert!(!solid.is_empty());
+    assert!(pixels(230..380).any(|(x, y)| at(x, y) == shadow));
+    for (x, y) in solid {
+        assert_ne!(at(x + 3, y + 3), BOARD, "no shadow under {:?}", (x, y));
+    }
+}
+
+#[test]
+fn played_card() {
+    let fb = render_offscreen(
+        vec![Drawable::PlayedCard(
+            Rect::new(100, 100, 213, 180),
+            "Knight \n \n HP:5 | AC:1 | Upkeep: 1 \n Armored \n \n ATK: 3 \n Slash".to_string(),
+            (0, 0, 0, 255),
+            Some(DraggableSnapType::Card(true, false)),
+            TextStyle::default(),
+        )],
+        BOARD,
+    );
+    assert_golden(golden_dir(), "played_card", &fb, 2);
+}
+
+#[test]
+fn translucent_overlay() {
+    let fb = render_offscreen(
+        vec![
+            Drawable::Rectangle(Rect::new(10, 10, 200, 100), (255, 0, 0, 255), None),
+            Drawable::Rectangle(Rect::new(110, 60, 200, 100), (0, 0, 255, 128), None),
+            Drawable::Text(
+                Rect::new(10, 200, 600, 100),
+                "Half-covered text".to_string(),
+                FontId::CARD_BODY,
+                40.0,
+                TextStyle::default(),
+            ),
+            Drawable::Rectangle(Rect::new(10, 200, 300, 100), (0, 0, 0, 96), None),
+        ],
+        BOARD,
+    );
+    // half-alpha blue, source-over: 255 * 127/255 red left, 255 * 128/255 blue added
+    let at = |x: usize, y: usize| fb.pixels[y * fb.width + x];
+    assert_eq!(at(150, 80), (127, 0, 128, 255));
+    assert_eq!(at(250, 120), (45, 49, 184, 255));
+    // and the parts it doesn't cover are untouched
+    assert_eq!(at(50, 50), (255, 0, 0, 255));
+    assert_eq!(at(350, 120), BOARD);
+    assert_golden(golden_dir(), "translucent_overlay", &fb, 2);
+}
+
+// A 2x1 sheet of 4x4 cells: a red/white checker and a blue cell with a
+// transparent hole, so cropping, scaling and per-pixel alpha all show up.
+fn test_sheet() -> Rc<Image> {
+    let mut pixels = vec![];
+    for y in 0..4 {
+        for x in 0..8 {
+            pixels.push(if x < 4 {
+                if (x + y) % 2 == 0 {
+                    (255, 0, 0, 255)
+                } else {
+                    (255, 255, 255, 255)
+                }
+            } else if (1..3).contains(&(x - 4)) && (1..3).contains(&y) {
+                (0, 0, 0, 0)
+            } else {
+                (0, 0, 255, 255)
+            });
+        }
+    }
+    Rc::new(Image::new(pixels, 8, 4))
+}
+
+#[test]
+fn sprite() {
+    let sheet = test_sheet();
+    let checker = Sprite::new(sheet.clone()).sheet_cell(4, 4, 0, 0);
+    let ring = Sprite::new(sheet).sheet_cell(4, 4, 1, 0);
+    let fb = render_offscreen(
+        vec![
+            Drawable::Sprite(checker.natural_rect(10, 10), checker.clone(), None),
+            Drawable::Sprite(Rect::new(30, 10, 64, 64), checker, None),
+            Drawable::Sprite(Rect::new(110, 10, 64, 64), ring.clone(), None),
+            Drawable::Sprite(Rect::new(190, 10, 64, 64), ring.with_opacity(128), None),
+        ],
+        BOARD,
+    );
+    let at = |x: usize, y: usize| fb.pixels[y * fb.width + x];
+    // one pixel per texel at natural size, 16 per texel scaled to 64x64
+    assert_eq!(at(10, 10), (255, 0, 0, 255));
+    assert_eq!(at(11, 10), (255, 255, 255, 255));
+    assert_eq!(at(30 + 8, 10 + 8), (255, 0, 0, 255));
+    assert_eq!(at(30 + 24, 10 + 8), (255, 255, 255, 255));
+    // the ring's edge is blue and its hole lets the board through
+    assert_eq!(at(110 + 8, 10 + 8), (0, 0, 255, 255));
+    assert_eq!(at(110 + 32, 10 + 32), BOARD);
+    // at half opacity the blue is blended over the board
+    assert_eq!(at(190 + 8, 10 + 8), (45, 49, 184, 255));
+    assert_eq!(at(190 + 32, 10 + 32), BOARD);
+    assert_golden(golden_dir(), "sprite", &fb, 0);
+}
+
+// Shapes and text hanging off every edge get cut off instead of wrapping or
+// panicking, and nested clips only draw where both overlap.
+#[test]
+fn clipping() {
+    let fb = render_offscreen(
+        vec![
+            Drawable::Rectangle(Rect::new(1800, 1000, 300, 300), (255, 0, 0, 255), None),
+            Drawable::RectOutlined(Rect::new(1850, 10, 400, 100), (255, 255, 255, 255), None),
+            Drawable::Text(
+                Rect::new(1700, 500, 600, 100),
+                "Off the edge".to_string(),
+                FontId::GAME_TITLE,
+                60.0,
+                TextStyle::default(),
+            ),
+            Drawable::Clipped(
+                Rect::new(100, 100, 200, 200),
+                vec![
+                    Drawable::Rectangle(Rect::new(50, 50, 300, 300), (0, 0, 255, 255), None),
+                    Drawable::Clipped(
+                        Rect::new(200, 200, 400, 400),
+                        vec![Drawable::Rectangle(Rect::new(0, 0, 1000, 1000), (0, 255, 0, 255), None)],
+                    ),
+                ],
+            ),
+        ],
+        BOARD,
+    );
+    let at = |x: usize, y: usize| fb.pixels[y * fb.width + x];
+    let count = |c: Color| fb.pixels.iter().filter(|&&p| p == c).count();
+    // only the on-screen 120x80 of the red square
+    assert_eq!(count((255, 0, 0, 255)), 120 * 80);
+    // blue stays inside the outer clip even though it's bigger
+    assert_eq!(at(150, 150), (0, 0, 255, 255));
+    assert_eq!(at(99, 150), BOARD);
+    assert_eq!(at(150, 300), BOARD);
+    // and the green fills just the overlap of both clips, 200..300 each way
+    assert_eq!(count((0, 255, 0, 255)), 100 * 100);
+    assert_eq!(at(200, 200), (0, 255, 0, 255));
+    assert_eq!(at(299, 299), (0, 255, 0, 255));
+    assert_eq!(at(199, 250), (0, 0, 255, 255));
+    assert_eq!(at(300, 250), BOARD);
+    assert_golden(golden_dir(), "clipping", &fb, 2);
+}
+
+// Moving a clipped group has to carry its text along, or the text gets left
+// behind and clipped away by the group's new rect.
+#[test]
+fn moved_clipped_text() {
+    let group = |x: usize, y: usize| {
+        Drawable::Clipped(
+            Rect::new(x, y, 300, 100),
+            vec![Drawable::Text(
+                Rect::new(x + 10, y + 10, 280, 80),
+                "Moved along".to_string(),
+                FontId::CARD_BODY,
+                40.0,
+                TextStyle::default(),
+            )],
+        )
+    };
+    let expected = render_offscreen(vec![group(400, 300)], BOARD);
+    assert!(expected.pixels.iter().any(|&p| p != BOARD));
+
+    let mut shifted = group(100, 100);
+    shifted.shift((300, 200));
+    assert_eq!(render_offscreen(vec![shifted], BOARD).pixels, expected.pixels);
+
+    let mut moved = group(100, 100);
+    moved.move_to((400, 300));
+    assert_eq!(render_offscreen(vec![moved], BOARD).pixels, expected.pixels);
+}
+
+// The same two-line label in each corner and the middle of its box, plus a
+// spaced-out, mixed-size paragraph.
+#[test]
+fn text_layout() {
+    let mut drawables = vec![];
+    let aligns = [
+        (HAlign::Left, VAlign::Top),
+        (HAlign::Right, VAlign::Top),
+        (HAlign::Center, VAlign::Middle),
+        (HAlign::Left, VAlign::Bottom),
+        (HAlign::Right, VAlign::Bottom),
+    ];
+    for (h_align, v_align) in aligns {
+        drawables.push(Drawable::RectOutlined(Rect::new(50, 50, 500, 300), (255, 255, 255, 255), None));
+        drawables.push(Drawable::Text(
+            Rect::new(50, 50, 500, 300),
+            "Mana\nleft: 5".to_string(),
+            FontId::CARD_BODY,
+            30.0,
+            TextStyle::default().aligned(h_align, v_align),
+        ));
+    }
+
+    let paragraph = "Knight\nHP:5 | AC:1\nSlash";
+    let lines = line_ranges(paragraph);
+    drawables.push(Drawable::RectOutlined(Rect::new(700, 50, 300, 400), (255, 255, 255, 255), None));
+    drawables.push(Drawable::Text(
+        Rect::new(700, 50, 300, 400),
+        paragraph.to_string(),
+        FontId::CARD_BODY,
+        20.0,
+        TextStyle::default()
+            .aligned(HAlign::Center, VAlign::Top)
+            .with_line_spacing(1.5)
+            .with_span(lines[0].clone(), Some(36.0), Some(FontId::CARD_TITLE)),
+    ));
+
+    drawables.push(Drawable::Text(
+        Rect::new(1100, 50, 100, 100),
+        "Never wraps, however long it gets".to_string(),
+        FontId::CARD_BODY,
+        20.0,
+        TextStyle::default().with_wrap(Wrap::Off),
+    ));
+
+    let fb = render_offscreen(drawables, BOARD);
+    assert_golden(golden_dir(), "text_layout", &fb, 2);
+
+    // where the label's ink starts and ends, give or take the glyphs' side bearings
+    let state = setup_headless();
+    let mut cache = GlyphCache::new();
+    let r = Rect::new(50, 50, 500, 300);
+    let mut painted = |h_align, v_align| {
+        let style = TextStyle::default().aligned(h_align, v_align);
+        let s = "Mana\nleft: 5";
+        layout_text_bounds(&mut cache, s, r, &state.fonts, FontId::CARD_BODY, 30.0, &style)
+    };
+    let left = painted(HAlign::Left, VAlign::Top);
+    assert!((50..=53).contains(&left.x), "{:?}", left);
+    let right = painted(HAlign::Right, VAlign::Top);
+    assert!((547..=550).contains(&right.right()), "{:?}", right);
+    assert_eq!(right.w, left.w);
+    let center = painted(HAlign::Center, VAlign::Middle);
+    assert!((center.x + center.right()).abs_diff(2 * 300) <= 2, "{:?}", center);
+
+    // wrapping breaks lines exactly where the same text broken by hand does
+    let mut height = |s, w, style: &TextStyle| {
+        let r = Rect::new(0, 0, w, 400);
+        layout_text_bounds(&mut cache, s, r, &state.fonts, FontId::CARD_BODY, 20.0, style).h
+    };
+    let unwrapped = TextStyle::default().with_wrap(Wrap::Off);
+    assert_eq!(height(paragraph, 300, &TextStyle::default()), height(paragraph, 300, &unwrapped));
+    let long = "Never wraps, however long it gets";
+    let by_hand = "Never\nwraps,\nhowever\nlong it\ngets";
+    assert_eq!(height(long, 100, &TextStyle::default()), height(by_hand, 100, &unwrapped));
+    assert_eq!(height(long, 100, &unwrapped), height(long, 1000, &TextStyle::default()));
+}
+
+// One long card ability in three boxes: roomy enough at full size, shrunk to
+// fit, and cut off with an ellipsis at the minimum size.
+#[test]
+fn text_fit() {
+    let long = "Whenever this unit attacks, deal 2 damage to every enemy unit in the lane and heal the tower for 3.";
+    let mut drawables = vec![];
+    for r in [Rect::new(20, 20, 400, 400), Rect::new(450, 20, 200, 60), Rect::new(700, 20, 150, 30)] {
+        drawables.push(Drawable::RectOutlined(r, (255, 255, 255, 255), None));
+        drawables.push(Drawable::Text(
+            r,
+            long.to_string(),
+            FontId::CARD_BODY,
+            40.0,
+            TextStyle::default().fit_to_rect(10.0),
+        ));
+    }
+    let fb = render_offscreen(drawables, BOARD);
+    assert_golden(golden_dir(), "text_fit", &fb, 2);
+
+    let state = setup_headless();
+    let mut cache = GlyphCache::new();
+    let mut fitted = |r| {
+        let style = TextStyle::default().fit_to_rect(10.0);
+        let fonts = &state.fonts;
+        let glyphs = layout_text(&mut cache, long, r, fonts, FontId::CARD_BODY, 40.0, &style);
+        let size = glyphs.iter().map(|g| g.px).fold(0.0, f32::max);
+        (size, glyphs.iter().map(|g| g.c).collect::<String>())
+    };
+    // never bigger than asked for, and all of it when it fits
+    let (size, text) = fitted(Rect::new(20, 20, 400, 400));
+    assert_eq!((size, text.as_str()), (40.0, long));
+    let (size, text) = fitted(Rect::new(450, 20, 200, 60));
+    assert!((10.0..40.0).contains(&size), "{}", size);
+    assert_eq!(text, long);
+    // too small even at the minimum, so it's cut short
+    let (size, text) = fitted(Rect::new(700, 20, 150, 30));
+    assert_eq!(size, 10.0);
+    assert!(text.ends_with('\u{2026}'), "{:?}", text);
+    assert!(long.starts_with(text.trim_end_matches('\u{2026}')));
+}
+
+// Card text in markup: stat icons, a bold heading, and color and size spans,
+// on a played card and at a size where the icons are easy to see.
+#[test]
+fn markup() {
+    let description = "Knight\n\n[hp] 5  [def] 1  Upkeep [mana] 1\nArmored\n\n[atk] 3\nSlash\n\n\
+                       [b]Special[/b]  [mana] 2\n[color=gold]Shield[/color] bash";
+    let fb = render_offscreen(
+        vec![
+            Drawable::PlayedCard(
+                Rect::new(100, 100, 213, 180),
+                description.to_string(),
+                (0, 0, 0, 255),
+                None,
+                card_text_style(description),
+            ),
+            Drawable::Text(
+                Rect::new(400, 100, 600, 200),
+                "[hp] [color=#ff5050]2[/color] [def] 1\n\
+                 [atk] [size=60]12[/size] [mana] 4 [b]bold[/b] [[not a tag]"
+                    .to_string(),
+                FontId::GAME_TITLE,
+                40.0,
+                TextStyle::default()
+                    .with_outline((0, 0, 0, 255), 1)
+                    .with_markup(),
+            ),
+        ],
+        BOARD,
+    );
+    assert_golden(golden_dir(), "markup", &fb, 2);
+
+    // the red 2 is painted red and the 1 after it isn't
+    let s = "[hp] [color=#ff5050]2[/color] [def] 1";
+    let style = TextStyle::default().with_outline((0, 0, 0, 255), 1).with_markup();
+    let glyphs = glyph_boxes(s, Rect::new(400, 100, 600, 200), FontId::GAME_TITLE, 40.0, &style);
+    let red = (255, 80, 80, 255);
+    let (_, color, two) = glyphs.iter().find(|(c, _, _)| *c == '2').unwrap();
+    assert_eq!(*color, Some(red));
+    assert!(count_in(&fb, *two, red) > 0);
+    let (_, color, one) = glyphs.iter().find(|(c, _, _)| *c == '1').unwrap();
+    assert_eq!(*color, None);
+    assert_eq!(count_in(&fb, *one, red), 0);
+    assert!(count_in(&fb, *one, (255, 255, 255, 255)) > 0);
+}
+
+// Each glyph of s laid out in r, with its span color and where its ink lands.
+fn glyph_boxes(
+    s: &str,
+    r: Rect,
+    font: FontId,
+    size: f32,
+    style: &TextStyle,
+) -> Vec<(char, Option<Color>, Rect)> {
+    let state = setup_headless();
+    let mut cache = GlyphCache::new();
+    let glyphs = layout_text(&mut cache, s, r, &state.fonts, font, size, style);
+    glyphs
+        .iter()
+        .map(|g| {
+            let metrics = cache.glyph(state.fonts.font(g.font), g.c, g.px).metrics;
+            let (x, y) = ((r.x as f32 + g.x).floor(), (r.y as f32 + g.y).floor());
+            (g.c, g.color, Rect::new(x as usize, y as usize, metrics.width, metrics.height))
+        })
+        .collect()
+}
+
+fn count_in(fb: &Frame, r: Rect, c: Color) -> usize {
+    (r.y..r.bottom())
+        .flat_map(|y| (r.x..r.right()).map(move |x| (x, y)))
+        .filter(|&(x, y)| fb.pixels[y * fb.width + x] == c)
+        .count()
+}
+
+// A span whose ends land inside multibyte characters covers the characters
+// those ends fall in, the same as spelling out their boundaries.
+#[test]
+fn spans_inside_multibyte_characters() {
+    let s = "Ünïcödé spell";
+    let text = |range| {
+        let span = TextSpan {
+            color: Some((255, 80, 80, 255)),
+            ..TextSpan::new(range)
+        };
+        Drawable::Text(
+            Rect::new(50, 50, 600, 100),
+            s.to_string(),
+            FontId::CARD_BODY,
+            40.0,
+            TextStyle::default().with_styled_span(span),
+        )
+    };
+    // Ü is bytes 0..2 and ï 3..5
+    let expected = render_offscreen(vec![text(0..3)], BOARD);
+    assert!(expected.pixels.contains(&(255, 80, 80, 255)));
+    assert_eq!(render_offscreen(vec![text(1..4)], BOARD).pixels, expected.pixels);
+}
+
+// Fonts loaded by name at runtime draw like any other, and loading over a
+// built-in name swaps the font for everything that uses it.
+#[test]
+fn runtime_fonts() {
+    let fonts_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../resources/fonts");
+    let roboto = fonts_dir.join("RobotoMono-Regular.ttf");
+    let mut state = setup_headless();
+    let headline = state.fonts.load("headline", &roboto).unwrap();
+    assert_eq!(state.fonts.get("headline"), Some(headline));
+    assert_eq!(state.fonts.load("game_title", &roboto).unwrap(), FontId::GAME_TITLE);
+    assert!(state.fonts.load("broken", "Cargo.toml").is_err());
+
+    let text = |font, y| {
+        Drawable::Text(
+            Rect::new(100, y, 600, 100),
+            "Player 1 has 5 mana".to_string(),
+            font,
+            40.0,
+            TextStyle::default(),
+        )
+    };
+    state.bg_color = BOARD;
+    state.drawables = vec![text(headline, 100), text(FontId::GAME_TITLE, 220)].into();
+    draw(&mut state);
+
+    let expected = render_offscreen(
+        vec![text(FontId::CARD_BODY, 100), text(FontId::CARD_BODY, 220)],
+        BOARD,
+    );
+    assert_eq!(compare_images(&state.fb2d, &expected.pixels, 0).mismatched_pixels, 0);
+}
+
+// A rect sized by measure_text holds the text without it wrapping any
+// differently. Glyphs can overhang their advance by a pixel or so.
+#[test]
+fn measured_text_fits() {
+    let state = setup_headless();
+    let mut cache = GlyphCache::new();
+    let style = TextStyle::default().aligned(HAlign::Center, VAlign::Middle);
+    let s = "Player 2 has fallen. Player 1 Wins!";
+    for max_width in [None, Some(300)] {
+        let metrics = measure_text(&state.fonts, s, FontId::GAME_TITLE, 60.0, max_width, &style);
+        let r = metrics.rect_at(100, 100);
+        let again = measure_text(&state.fonts, s, FontId::GAME_TITLE, 60.0, Some(r.w), &style);
+        assert_eq!(again.lines, metrics.lines);
+
+        let painted =
+            layout_text_bounds(&mut cache, s, r, &state.fonts, FontId::GAME_TITLE, 60.0, &style);
+        let slop = Rect::new(r.x - 2, r.y - 2, r.w + 4, r.h + 4);
+        assert_eq!(slop.union(&painted), slop);
+    }
+    assert_eq!(measure_text(&state.fonts, s, FontId::GAME_TITLE, 60.0, Some(300), &style).lines, 5);
+    assert_eq!(measure_text(&state.fonts, "a\nb", FontId::CARD_BODY, 12.0, None, &style).lines, 2);
+}
+
+// Carter One has no Cyrillic or Greek, so those letters come from the built-in
+// fallback (Roboto Mono) while the Latin ones stay in Carter One.
+#[test]
+fn font_fallback() {
+    let state = setup_headless();
+    let s = "Рыцарь (Knight) αβγ";
+    let style = TextStyle::default();
+    let metrics = measure_text(&state.fonts, s, FontId::GAME_TITLE, 40.0, None, &style);
+    for glyph in metrics.glyphs.iter().filter(|g| !g.c.is_whitespace()) {
+        let expected = if glyph.c.is_ascii() { FontId::GAME_TITLE } else { FontId::CARD_BODY };
+        assert_eq!(glyph.font, expected, "{:?}", glyph.c);
+    }
+
+    let fb = render_offscreen(
+        vec![Drawable::Text(
+            Rect::new(100, 100, 800, 100),
+            s.to_string(),
+            FontId::GAME_TITLE,
+            40.0,
+            TextStyle::default().with_shadow((0, 0, 0, 160), (2, 2)),
+        )],
+        BOARD,
+    );
+    assert_golden(golden_dir(), "font_fallback", &fb, 2);
+}
+
+// Every vector primitive, filled and outlined, plus an arrow.
+fn sample_shapes(anti_alias: bool) -> Vec<Drawable> {
+    let style = |style: ShapeStyle| if anti_alias { style.anti_aliased() } else { style };
+    let red = (220, 50, 50, 255);
+    let white = (255, 255, 255, 255);
+    let mut drawables = vec![
+        Drawable::Line((20, 20), (200, 120), 1, style(ShapeStyle::filled(white))),
+        Drawable::Line((20, 60), (200, 160), 8, style(ShapeStyle::filled(red))),
+        Drawable::Line(
+            (20, 120),
+            (200, 220),
+            14,
+            style(ShapeStyle::outlined((255, 255, 0, 255), 2)),
+        ),
+        Drawable::Ellipse(
+            circle_rect((280, 80), 50),
+            style(ShapeStyle::filled((70, 130, 255, 255))),
+            None,
+        ),
+        Drawable::Ellipse(Rect::new(230, 160, 140, 80), style(ShapeStyle::outlined(white, 3)), None),
+        Drawable::Polygon(
+            vec![(400, 20), (480, 60), (460, 140), (390, 120)],
+            style(ShapeStyle::filled((90, 220, 90, 255))),
+            None,
+        ),
+        Drawable::Polygon(
+            vec![(400, 160), (390, 260), (490, 260)],
+            style(ShapeStyle::outlined((255, 215, 0, 255), 2)),
+            None,
+        ),
+        Drawable::RoundedRect(
+            Rect::new(520, 20, 150, 110),
+            20,
+            style(ShapeStyle::filled((0, 0, 0, 160))),
+            None,
+        ),
+        Drawable::RoundedRect(
+            Rect::new(520, 160, 150, 110),
+            12,
+            style(ShapeStyle::outlined(white, 4)),
+            None,
+        ),
+    ];
+    drawables.append(&mut arrow((720, 250), (900, 60), 6, style(ShapeStyle::filled(red))));
+    drawables
+}
+
+#[test]
+fn shapes() {
+    let drawables = sample_shapes(false);
+
+    // hit testing follows the shape, not its bounding box
+    assert!(drawables[3].contains((280, 80)));
+    assert!(!drawables[3].contains((235, 35)));
+    assert!(!drawables[4].contains((300, 200)));
+
+    let fb = render_offscreen(drawables, BOARD);
+    assert_golden(golden_dir(), "shapes", &fb, 2);
+
+    // without anti-aliasing every pixel is either the shape or the board
+    for (window, fill) in edge_windows() {
+        let (solid, blended) = edge_pixels(&fb, window, fill);
+        assert!(solid > 0 && blended == 0, "{:?}: {} solid, {} blended", window, solid, blended);
+    }
+}
+
+#[test]
+fn shapes_anti_aliased() {
+    let fb = render_offscreen(sample_shapes(true), BOARD);
+    assert_golden(golden_dir(), "shapes_anti_aliased", &fb, 2);
+
+    // edges are partly covered, so somewhere between the board and the shape
+    for (window, fill) in edge_windows() {
+        let (solid, blended) = edge_pixels(&fb, window, fill);
+        assert!(solid > 0 && blended > 0, "{:?}: {} solid, {} blended", window, solid, blended);
+    }
+}
+
+// Bits of sample_shapes with nothing else nearby: the filled circle, and the
+// middle of the one pixel line.
+fn edge_windows() -> [(Rect, Color); 2] {
+    [
+        (Rect::new(226, 26, 108, 108), (70, 130, 255, 255)),
+        (Rect::new(100, 58, 20, 26), (255, 255, 255, 255)),
+    ]
+}
+
+// How many pixels in window are exactly fill, and how many are strictly
+// between it and the board. Panics on anything else.
+fn edge_pixels(fb: &Frame, window: Rect, fill: Color) -> (usize, usize) {
+    let (mut solid, mut blended) = (0, 0);
+    for y in window.y..window.bottom() {
+        for x in window.x..window.right() {
+            let p = fb.pixels[y * fb.width + x];
+            if p == fill {
+                solid += 1;
+            } else if p != BOARD {
+                let within = |p: u8, a: u8, b: u8| a.min(b) <= p && p <= a.max(b);
+                let ok = within(p.0, BOARD.0, fill.0)
+                    && within(p.1, BOARD.1, fill.1)
+                    && within(p.2, BOARD.2, fill.2);
+                assert!(ok, "{:?} at {:?} isn't a blend of {:?} and the board", p, (x, y), fill);
+                blended += 1;
+            }
+        }
+    }
+    (solid, blended)
+}
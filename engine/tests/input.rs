@@ -0,0 +1,219 @@
+use engine::{
+    begin_frame, handle_winit_event, setup_headless, update_hover, Binding, ControlFlow, Drawable,
+    Event, HoverEvent, InputMap, MouseButton, MouseScrollDelta, Rect, Recording, State,
+    VirtualKeyCode, WindowEvent,
+};
+use rand::Rng;
+use std::io::ErrorKind;
+use std::time::Duration;
+use winit::dpi::PhysicalPosition;
+
+fn controls() -> InputMap {
+    InputMap::new()
+        .with("end_turn", &[Binding::Key(VirtualKeyCode::Space), Binding::Mouse(MouseButton::Left)])
+        .with("spend_mana", &[Binding::Key(VirtualKeyCode::Down)])
+}
+
+#[test]
+fn actions_have_edges() {
+    let mut state = setup_headless();
+    state.input_map = controls();
+    assert!(!state.action_down("end_turn"));
+
+    state.now_keys[VirtualKeyCode::Space as usize] = true;
+    assert!(state.action_down("end_turn"));
+    assert!(state.action_just_pressed("end_turn"));
+    assert!(!state.action_just_pressed("spend_mana"));
+
+    // next frame, still held, and now the mouse too: not a new press
+    state.prev_keys.copy_from_slice(&state.now_keys);
+    state.set_mouse_button(MouseButton::Left, true);
+    assert!(state.action_down("end_turn"));
+    assert!(!state.action_just_pressed("end_turn"));
+
+    state.prev_mouse_buttons.copy_from_slice(&state.now_mouse_buttons);
+    state.now_keys[VirtualKeyCode::Space as usize] = false;
+    state.set_mouse_button(MouseButton::Left, false);
+    assert!(state.action_just_released("end_turn"));
+    assert!(!state.action_down("unbound"));
+}
+
+#[test]
+fn controls_file_overrides_defaults() {
+    let file = r#"{ "end_turn": ["Return", "MouseRight"], "spend_mana": [] }"#;
+    let loaded = InputMap::from_json(file).unwrap();
+    assert_eq!(
+        loaded.bindings("end_turn"),
+        &[Binding::Key(VirtualKeyCode::Return), Binding::Mouse(MouseButton::Right)]
+    );
+
+    let dir = std::env::temp_dir().join(format!("engine-input-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("controls.json");
+    std::fs::write(&path, file).unwrap();
+
+    let mut map = controls().with("pause", &[Binding::Key(VirtualKeyCode::Escape)]);
+    map.load_overrides(&path).unwrap();
+    assert_eq!(map.bindings("end_turn"), loaded.bindings("end_turn"));
+    assert!(map.bindings("spend_mana").is_empty());
+    assert_eq!(map.bindings("pause"), &[Binding::Key(VirtualKeyCode::Escape)]);
+
+    // saving and loading gets the same map back
+    map.save(&path).unwrap();
+    assert_eq!(InputMap::load(&path).unwrap(), map);
+
+    // a missing file keeps the defaults, a broken one is an error
+    let mut defaults = controls();
+    defaults.load_overrides(dir.join("missing.json")).unwrap();
+    assert_eq!(defaults, controls());
+    let bad = InputMap::from_json(r#"{ "end_turn": ["Spacebar"] }"#).unwrap_err();
+    assert_eq!(bad.kind(), ErrorKind::InvalidData);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn every_mouse_button_has_edges() {
+    let mut state = setup_headless();
+    for button in [MouseButton::Right, MouseButton::Middle, MouseButton::Other(4)] {
+        state.set_mouse_button(button, true);
+        assert!(state.mouse_just_pressed(button));
+    }
+    assert!(!state.mouse_down(MouseButton::Left));
+    // too far out to track, so never down
+    state.set_mouse_button(MouseButton::Other(500), true);
+    assert!(!state.mouse_down(MouseButton::Other(500)));
+
+    state.prev_mouse_buttons.copy_from_slice(&state.now_mouse_buttons);
+    state.set_mouse_button(MouseButton::Right, false);
+    assert!(state.mouse_just_released(MouseButton::Right));
+    assert!(state.mouse_down(MouseButton::Middle));
+    assert!(!state.mouse_just_pressed(MouseButton::Middle));
+
+    // the action layer sees them too
+    state.input_map = InputMap::new().with("inspect", &[Binding::Mouse(MouseButton::Right)]);
+    assert!(state.action_just_released("inspect"));
+}
+
+#[test]
+fn wheel_adds_up_over_a_frame() {
+    let mut state = setup_headless();
+    state.scroll(MouseScrollDelta::LineDelta(0.0, 1.0));
+    state.scroll(MouseScrollDelta::LineDelta(0.0, 2.0));
+    state.scroll(MouseScrollDelta::PixelDelta(PhysicalPosition::new(-40.0, 0.0)));
+    assert_eq!(state.wheel_delta, (-2.0, 3.0));
+}
+
+#[test]
+fn hover_enters_and_leaves_the_topmost_drawable() {
+    let mut state = setup_headless();
+    let rect = |r, c| Drawable::Rectangle(r, c, None);
+    let card = state.drawables.insert(rect(Rect::new(100, 100, 200, 300), (0, 0, 0, 255)));
+    let badge = state.drawables.insert(rect(Rect::new(250, 100, 50, 50), (255, 0, 0, 255)));
+
+    state.mouse_coords = (150, 200);
+    assert_eq!(update_hover(&mut state), vec![HoverEvent::Enter(card)]);
+    assert_eq!(update_hover(&mut state), vec![]);
+
+    state.mouse_coords = (260, 110);
+    assert_eq!(update_hover(&mut state), vec![HoverEvent::Leave(card), HoverEvent::Enter(badge)]);
+    assert_eq!(state.hovered, Some(badge));
+
+    state.mouse_coords = (900, 900);
+    assert_eq!(update_hover(&mut state), vec![HoverEvent::Leave(badge)]);
+    assert_eq!(state.hovered, None);
+}
+
+// Everything a game could see in a frame, plus a draw from its rng.
+fn frame_snapshot(state: &mut State) -> (bool, bool, (usize, usize), (f32, f32), Duration, u32) {
+    (
+        state.now_keys[VirtualKeyCode::Space as usize],
+        state.mouse_down(MouseButton::Right),
+        state.mouse_coords,
+        state.wheel_delta,
+        state.clock,
+        state.rng.gen(),
+    )
+}
+
+// The window the headless renderer reports is the framebuffer's size, so
+// window pixels are framebuffer pixels.
+#[allow(deprecated)]
+fn move_cursor(state: &mut State, (x, y): (usize, usize)) {
+    let event = Event::WindowEvent {
+        window_id: unsafe { winit::window::WindowId::dummy() },
+        event: WindowEvent::CursorMoved {
+            device_id: unsafe { winit::event::DeviceId::dummy() },
+            position: PhysicalPosition::new(x as f64, y as f64),
+            modifiers: Default::default(),
+        },
+    };
+    handle_winit_event(event, &mut ControlFlow::Poll, state);
+}
+
+#[test]
+fn moving_the_cursor_reports_hover_changes() {
+    let mut state = setup_headless();
+    let rect = |r, c| Drawable::Rectangle(r, c, None);
+    let card = state.drawables.insert(rect(Rect::new(100, 100, 200, 300), (0, 0, 0, 255)));
+    let badge = state.drawables.insert(rect(Rect::new(250, 100, 50, 50), (255, 0, 0, 255)));
+
+    begin_frame(&mut state);
+    move_cursor(&mut state, (150, 200));
+    move_cursor(&mut state, (160, 210));
+    assert_eq!(state.hover_events, vec![HoverEvent::Enter(card)]);
+    assert_eq!(state.mouse_coords, (160, 210));
+
+    // everything since the frame started, in order
+    begin_frame(&mut state);
+    assert!(state.hover_events.is_empty());
+    move_cursor(&mut state, (260, 110));
+    move_cursor(&mut state, (900, 900));
+    assert_eq!(
+        state.hover_events,
+        vec![HoverEvent::Leave(card), HoverEvent::Enter(badge), HoverEvent::Leave(badge)]
+    );
+    assert_eq!(state.hovered, None);
+}
+
+#[test]
+fn replay_reproduces_input_time_and_rng() {
+    let path = std::env::temp_dir().join(format!("engine-replay-{}.jsonl", std::process::id()));
+
+    let mut live = setup_headless();
+    live.start_recording(&path).unwrap();
+    let mut recorded = vec![];
+    for i in 0..5 {
+        begin_frame(&mut live);
+        live.now_keys[VirtualKeyCode::Space as usize] = i % 2 == 0;
+        live.set_mouse_button(MouseButton::Right, i == 3);
+        live.mouse_coords = (10 * i, 20 * i);
+        live.scroll(MouseScrollDelta::LineDelta(0.0, i as f32));
+        recorded.push(frame_snapshot(&mut live));
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    // the last frame is written when the next one starts
+    begin_frame(&mut live);
+    live.stop_recording();
+
+    let recording = Recording::load(&path).unwrap();
+    assert_eq!(recording.seed, live.seed);
+    assert_eq!(recording.resolution, live.screen_size());
+    assert_eq!(recording.frames.len(), 5);
+
+    let mut replayed = setup_headless();
+    replayed.start_replay(&path).unwrap();
+    assert!(replayed.is_replaying());
+    let mut played = vec![];
+    for _ in 0..5 {
+        begin_frame(&mut replayed);
+        played.push(frame_snapshot(&mut replayed));
+    }
+    assert_eq!(played, recorded);
+
+    // out of frames, back to live input
+    begin_frame(&mut replayed);
+    assert!(!replayed.is_replaying());
+
+    std::fs::remove_file(&path).unwrap();
+}
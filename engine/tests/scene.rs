@@ -0,0 +1,187 @@
+use engine::golden::{compare_images, render_offscreen};
+use engine::{
+    check_and_handle_drag, draw, draw_order, setup_headless, Color, DraggableSnapType, Drawable,
+    DrawableId, Drawables, FontId, Layer, MouseButton, Rect, Scene, TextStyle,
+};
+
+const BOARD: Color = (91, 99, 112, 255);
+
+fn board() -> Vec<Drawable> {
+    vec![
+        Drawable::Rectangle(Rect::new(0, 0, 1920, 200), (40, 40, 60, 255), None),
+        Drawable::PlayedCard(
+            Rect::new(100, 300, 213, 180),
+            "Knight \n \n HP:5 | AC:1 | Upkeep: 1".to_string(),
+            (0, 0, 0, 255),
+            None,
+            TextStyle::default(),
+        ),
+        Drawable::Rectangle(Rect::new(250, 350, 200, 100), (0, 0, 255, 128), None),
+        Drawable::Text(
+            Rect::new(600, 600, 600, 100),
+            "Player 1 has 5 mana".to_string(),
+            FontId::GAME_TITLE,
+            40.0,
+            TextStyle::default().with_shadow((0, 0, 0, 160), (2, 2)),
+        ),
+    ]
+}
+
+// Moving, editing and removing drawables between frames has to leave exactly
+// the same pixels as drawing the final frame from scratch.
+#[test]
+fn incremental_redraw_matches_full_redraw() {
+    let mut state = setup_headless();
+    state.bg_color = BOARD;
+    let ids: Vec<DrawableId> = board().into_iter().map(|d| state.drawables.insert(d)).collect();
+    draw(&mut state);
+
+    state.drawables[ids[1]].move_to((700, 250));
+    state.drawables[ids[3]] = Drawable::Text(
+        Rect::new(600, 600, 600, 100),
+        "Player 1 has 12 mana".to_string(),
+        FontId::GAME_TITLE,
+        40.0,
+        TextStyle::default().with_shadow((0, 0, 0, 160), (2, 2)),
+    );
+    draw(&mut state);
+
+    state.drawables.remove(ids[2]);
+    draw(&mut state);
+
+    let expected = render_offscreen(state.drawables.values().cloned().collect(), BOARD);
+    let diff = compare_images(&state.fb2d, &expected.pixels, 0);
+    assert_eq!(diff.mismatched_pixels, 0);
+}
+
+#[test]
+fn unchanged_frame_has_nothing_to_repaint() {
+    let mut scene = Scene::new(1920, 1080);
+    let drawables = board();
+
+    let first = scene.update(&drawables, BOARD, |d| d.get_rect());
+    assert_eq!(first, vec![Rect::new(0, 0, 1920, 1080)]);
+
+    let second = scene.update(&drawables, BOARD, |d| d.get_rect());
+    assert!(second.is_empty());
+
+    let mut moved = drawables.clone();
+    moved[2].move_to((260, 350));
+    let third = scene.update(&moved, BOARD, |d| d.get_rect());
+    assert_eq!(third, vec![Rect::new(250, 350, 210, 100)]);
+}
+
+// Replacing a font changes how text drawn with it looks even though the
+// drawables are the same, so the next frame has to repaint it.
+#[test]
+fn swapping_a_font_repaints_its_text() {
+    let mut state = setup_headless();
+    state.bg_color = BOARD;
+    for d in board() {
+        state.drawables.insert(d);
+    }
+    draw(&mut state);
+    let before = state.fb2d.clone();
+
+    let roboto = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../resources/fonts/RobotoMono-Regular.ttf");
+    state.fonts.load("game_title", roboto).unwrap();
+    draw(&mut state);
+    assert!(compare_images(&state.fb2d, &before, 0).mismatched_pixels > 0);
+
+    // and it looks just like drawing it fresh with the new font
+    let expected: Vec<Drawable> = board()
+        .into_iter()
+        .map(|d| match d {
+            Drawable::Text(r, s, FontId::GAME_TITLE, size, style) => {
+                Drawable::Text(r, s, FontId::CARD_BODY, size, style)
+            }
+            d => d,
+        })
+        .collect();
+    let expected = render_offscreen(expected, BOARD);
+    assert_eq!(compare_images(&state.fb2d, &expected.pixels, 0).mismatched_pixels, 0);
+}
+
+// Layers beat z-index, z-index beats list order, and ties stay in list order.
+#[test]
+fn layers_paint_in_order() {
+    let red = Drawable::Rectangle(Rect::new(100, 100, 200, 200), (255, 0, 0, 255), None);
+    let green = Drawable::Rectangle(Rect::new(150, 150, 200, 200), (0, 255, 0, 255), None);
+    let blue = Drawable::Rectangle(Rect::new(200, 200, 200, 200), (0, 0, 255, 255), None);
+    let layered = vec![
+        red.clone().on_layer(Layer::Ui, 0),
+        blue.clone().on_layer(Layer::Board, 5),
+        green.clone(),
+    ];
+    assert_eq!(draw_order(&layered, None), vec![2, 1, 0]);
+
+    let expected = render_offscreen(vec![green, blue, red], BOARD);
+    let actual = render_offscreen(layered, BOARD);
+    let diff = compare_images(&actual.pixels, &expected.pixels, 0);
+    assert_eq!(diff.mismatched_pixels, 0);
+}
+
+// Clicking picks whatever is drawn on top, and the picked card is drawn above
+// everything but the overlay until it's dropped.
+#[test]
+fn dragged_card_stays_on_top() {
+    let card = |x| {
+        Drawable::Rectangle(
+            Rect::new(x, 100, 200, 300),
+            (0, 0, 0, 255),
+            Some(DraggableSnapType::Card(true, false)),
+        )
+    };
+    let mut state = setup_headless();
+    let top = state.drawables.insert(card(100).on_layer(Layer::Cards, 0));
+    let bottom = state.drawables.insert(card(150));
+    let hud = state
+        .drawables
+        .insert(Drawable::Rectangle(Rect::new(0, 0, 1920, 50), (40, 40, 60, 255), None).on_layer(Layer::Ui, 0));
+
+    state.mouse_coords = (200, 200);
+    state.set_mouse_button(MouseButton::Left, true);
+    check_and_handle_drag(&mut state);
+    assert_eq!(state.drag_item_id, Some(top));
+    assert_eq!(state.drawables.draw_order(state.drag_item_id), vec![bottom, hud, top]);
+
+    state.prev_mouse_buttons.copy_from_slice(&state.now_mouse_buttons);
+    state.mouse_coords = (300, 250);
+    check_and_handle_drag(&mut state);
+    assert_eq!(state.drawables[top].get_coords(), (200, 150));
+    assert_eq!(state.drawables[top].layer(), Layer::Cards);
+
+    state.prev_mouse_buttons.copy_from_slice(&state.now_mouse_buttons);
+    state.set_mouse_button(MouseButton::Left, false);
+    check_and_handle_drag(&mut state);
+    assert_eq!(state.drag_item_id, None);
+    assert_eq!(state.drawables.draw_order(None), vec![bottom, top, hud]);
+}
+
+// Ids keep pointing at the same drawable whatever happens around them, and
+// stop working once it's removed, even after its slot is reused.
+#[test]
+fn drawable_ids_survive_removal() {
+    let mut drawables = Drawables::new();
+    let ids: Vec<DrawableId> = board().into_iter().map(|d| drawables.insert(d)).collect();
+    let mana = ids[3];
+
+    drawables.remove(ids[0]);
+    drawables.remove(ids[2]);
+    assert!(matches!(drawables[mana], Drawable::Text(..)));
+    assert_eq!(drawables.len(), 2);
+
+    let reused = drawables.insert(Drawable::Rectangle(Rect::new(0, 0, 10, 10), BOARD, None));
+    assert_eq!(drawables.get(ids[2]), None);
+    assert_eq!(drawables.get(ids[0]), None);
+    assert!(drawables.remove(ids[2]).is_none());
+    assert!(drawables.replace(ids[0], board().remove(0)).is_none());
+
+    // new drawables still go on top of the old ones
+    assert_eq!(drawables.ids().collect::<Vec<_>>(), vec![ids[1], mana, reused]);
+
+    drawables.clear();
+    assert!(drawables.is_empty());
+    assert!(drawables.get(mana).is_none());
+}
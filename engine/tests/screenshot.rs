@@ -0,0 +1,33 @@
+use engine::{draw, setup_headless, Drawable, Rect};
+use std::fs::File;
+
+#[test]
+fn screenshot_round_trips_the_framebuffer() {
+    let dir = std::env::temp_dir().join(format!("engine-screenshot-{}", std::process::id()));
+    let mut state = setup_headless();
+    state.bg_color = (0, 0, 0, 255);
+    state.drawables.insert(Drawable::Rectangle(Rect::new(37, 21, 1, 1), (200, 100, 50, 255), None));
+    draw(&mut state);
+
+    state.screenshot_dir = dir.clone();
+    let path = state.save_timestamped_screenshot().unwrap();
+    assert!(path.starts_with(&dir));
+
+    let decoder = png::Decoder::new(File::open(&path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).unwrap();
+    let (width, height) = state.screen_size();
+    assert_eq!((info.width as usize, info.height as usize), (width, height));
+    assert_eq!(info.color_type, png::ColorType::Rgb);
+    let rgb = |x: usize, y: usize| {
+        let i = (y * width + x) * 3;
+        (data[i], data[i + 1], data[i + 2])
+    };
+    assert_eq!(rgb(37, 21), (200, 100, 50));
+    assert_eq!(rgb(38, 21), (0, 0, 0));
+    // same bytes as encoding it in memory
+    assert_eq!(std::fs::read(&path).unwrap(), state.screenshot_png().unwrap());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,159 @@
+use engine::{begin_frame, draw, setup_headless, Drawable, MouseButton, Rect, Response, State, Ui};
+
+// Starts a frame with the mouse at mouse and the left button up or down.
+fn frame(state: &mut State, ui: &mut Ui, mouse: (usize, usize), down: bool) {
+    begin_frame(state);
+    state.mouse_coords = mouse;
+    state.set_mouse_button(MouseButton::Left, down);
+    ui.begin(state);
+}
+
+#[test]
+fn button_clicks_on_release_over_it() {
+    let mut state = setup_headless();
+    let mut ui = Ui::new();
+    let rect = Rect::new(100, 100, 200, 60);
+
+    frame(&mut state, &mut ui, (10, 10), false);
+    assert_eq!(ui.button(&mut state, rect, "End turn"), Response::default());
+    assert!(!ui.wants_mouse());
+
+    frame(&mut state, &mut ui, (150, 120), true);
+    let held = ui.button(&mut state, rect, "End turn");
+    assert!(held.hovered && held.pressed && !held.clicked);
+    assert!(ui.wants_mouse());
+
+    frame(&mut state, &mut ui, (150, 120), false);
+    assert!(ui.button(&mut state, rect, "End turn").clicked);
+
+    // pressed on it but let go somewhere else: no click
+    frame(&mut state, &mut ui, (150, 120), true);
+    ui.button(&mut state, rect, "End turn");
+    frame(&mut state, &mut ui, (900, 900), false);
+    assert!(!ui.button(&mut state, rect, "End turn").clicked);
+
+    // and a press that starts off it doesn't click either
+    frame(&mut state, &mut ui, (900, 900), true);
+    ui.button(&mut state, rect, "End turn");
+    frame(&mut state, &mut ui, (150, 120), false);
+    assert!(!ui.button(&mut state, rect, "End turn").clicked);
+}
+
+#[test]
+fn disabled_widgets_ignore_the_mouse() {
+    let mut state = setup_headless();
+    let mut ui = Ui::new();
+    let rect = Rect::new(100, 100, 200, 60);
+    let mut checked = false;
+
+    for down in [true, false] {
+        frame(&mut state, &mut ui, (150, 120), down);
+        ui.set_enabled(false);
+        assert_eq!(ui.button(&mut state, rect, "End turn"), Response::default());
+        ui.checkbox(&mut state, Rect::new(100, 200, 200, 40), "Sound", &mut checked);
+        ui.set_enabled(true);
+    }
+    assert!(!checked);
+    // still in the way of whatever's under it
+    assert!(ui.wants_mouse());
+}
+
+#[test]
+fn checkbox_and_slider_edit_their_values() {
+    let mut state = setup_headless();
+    let mut ui = Ui::new();
+    let checkbox = Rect::new(100, 100, 300, 40);
+    let slider = Rect::new(100, 200, 220, 20);
+    let mut sound = false;
+    let mut volume = 0.5;
+
+    frame(&mut state, &mut ui, (300, 120), true);
+    ui.checkbox(&mut state, checkbox, "Sound", &mut sound);
+    frame(&mut state, &mut ui, (300, 120), false);
+    assert!(ui.checkbox(&mut state, checkbox, "Sound", &mut sound).changed);
+    assert!(sound);
+
+    // the knob's center runs from x + 10 to right - 10
+    frame(&mut state, &mut ui, (210, 210), true);
+    assert!(ui.slider(&mut state, slider, &mut volume, 0.0..=10.0).changed);
+    assert_eq!(volume, 5.0);
+    frame(&mut state, &mut ui, (1000, 900), true);
+    ui.slider(&mut state, slider, &mut volume, 0.0..=10.0);
+    assert_eq!(volume, 10.0);
+    frame(&mut state, &mut ui, (0, 210), false);
+    assert!(!ui.slider(&mut state, slider, &mut volume, 0.0..=10.0).changed);
+    assert_eq!(volume, 10.0);
+}
+
+#[test]
+fn zero_height_slider_lays_out_and_draws() {
+    let mut state = setup_headless();
+    let mut ui = Ui::new();
+    let slider = Rect::new(100, 200, 200, 0);
+    let mut volume = 0.0;
+
+    // nothing to click on, so it can't be grabbed, but it still draws
+    frame(&mut state, &mut ui, (200, 200), true);
+    assert!(!ui.slider(&mut state, slider, &mut volume, 0.0..=1.0).changed);
+    assert!(!state.drawables.is_empty());
+    draw(&mut state);
+
+    volume = 0.5;
+    frame(&mut state, &mut ui, (200, 200), false);
+    ui.slider(&mut state, slider, &mut volume, 0.0..=1.0);
+    draw(&mut state);
+}
+
+// A slider narrower than it is tall gets a knob that fits its width, not
+// one as big as its height, centered on the track.
+#[test]
+fn narrow_slider_keeps_its_knob_inside() {
+    let mut state = setup_headless();
+    let mut ui = Ui::new();
+    let slider = Rect::new(100, 200, 21, 60);
+
+    for mut volume in [0.0, 1.0] {
+        frame(&mut state, &mut ui, (900, 900), false);
+        ui.slider(&mut state, slider, &mut volume, 0.0..=1.0);
+        let knobs: Vec<Rect> = state
+            .drawables
+            .values()
+            .filter_map(|d| match d {
+                Drawable::Layered(_, _, inner) => match **inner {
+                    Drawable::Ellipse(r, _, _) => Some(r),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        assert!(!knobs.is_empty());
+        for knob in knobs {
+            assert!(knob.w <= slider.w, "{:?}", knob);
+            assert!(knob.y >= slider.y && knob.bottom() <= slider.bottom(), "{:?}", knob);
+        }
+        draw(&mut state);
+    }
+}
+
+#[test]
+fn widgets_are_rebuilt_every_frame() {
+    let mut state = setup_headless();
+    let mut ui = Ui::new();
+
+    frame(&mut state, &mut ui, (10, 10), false);
+    ui.panel(&mut state, Rect::new(0, 0, 400, 300));
+    ui.label(&mut state, Rect::new(10, 10, 380, 40), "Settings");
+    ui.progress_bar(&mut state, Rect::new(10, 60, 380, 20), 0.25);
+    let count = state.drawables.len();
+    assert!(count > 0);
+
+    frame(&mut state, &mut ui, (10, 10), false);
+    ui.panel(&mut state, Rect::new(0, 0, 400, 300));
+    ui.label(&mut state, Rect::new(10, 10, 380, 40), "Settings");
+    ui.progress_bar(&mut state, Rect::new(10, 60, 380, 20), 0.5);
+    assert_eq!(state.drawables.len(), count);
+
+    // a frame without them clears them away
+    frame(&mut state, &mut ui, (10, 10), false);
+    assert!(state.drawables.is_empty());
+}
@@ -1,46 +1,63 @@
 use engine::{
-    check_and_handle_drag, clear, draw, draw_layout_text, generate_battle_slots,
-    generate_deck_slots, handle_mana, handle_winit_event, load_cards_from_file, render_character,
-    setup, Color, DraggableSnapType, Drawable, Event, PlayedCard, Rect, VirtualKeyCode, FontFamily,
-    WindowEvent,
+    draw, generate_battle_slots, generate_deck_slots, handle_winit_event, load_cards_from_file,
+    setup_with, Binding, Color, DragDrop, DragEvent, Drawable, DrawableId, EngineConfig, Event,
+    InputMap, PlayedCard, Rect, HAlign, Layer, TextStyle, Ui, VAlign, VirtualKeyCode, FontId,
 };
-use std::cmp::max;
-use winit::event_loop::EventLoop;
-
-const WIDTH: usize = 1920;
-const HEIGHT: usize = 1080;
+use std::collections::HashSet;
 
 const BACKGROUND_COLOR: Color = (91, 99, 112, 255);
 
-const CARD_SIZE: (usize, usize) = (WIDTH / 9, HEIGHT / 6);
+// gold with a soft shadow so it reads on top of the board, centered in the
+// space next to the deck
+fn mana_text_style() -> TextStyle {
+    TextStyle::new((255, 215, 0, 255))
+        .with_shadow((0, 0, 0, 160), (2, 2))
+        .aligned(HAlign::Center, VAlign::Middle)
+}
+
+// Space ends the turn and Down spends a mana, unless controls.json says otherwise.
+fn default_controls() -> InputMap {
+    InputMap::new()
+        .with("end_turn", &[Binding::Key(VirtualKeyCode::Space)])
+        .with("spend_mana", &[Binding::Key(VirtualKeyCode::Down)])
+}
+
 const CARD_PADDING_BOTTOM: usize = 15;
 const CARD_PADDING_TOP: usize = 15;
 
-struct GameState {
-    dragged: String,
+// What a card in someone's hand carries while it's dragged
+#[derive(Clone, Debug)]
+struct HandCard {
+    player: usize,
+    cost: usize,
 }
 
 fn main() {
     let mut turn: usize = 0;
 
-    let mut p1_m_idx: usize = 0;
-    let mut p2_m_idx: usize = 0;
-
     let mut p1_mana: usize = 5;
     let mut p2_mana: usize = 5;
 
     let mut deck1 = load_cards_from_file("../cards2.json");
-    deck1.shuffle();
-
     let mut deck2 = load_cards_from_file("../cards2.json");
-    deck2.shuffle();
 
-    let c1 = (255, 0, 0, 0);
-    let c2 = (0, 255, 0, 0);
-
-    let mut state = setup();
+    // ENGINE_RESOLUTION=1280x720 picks the framebuffer size; everything below scales with it
+    let mut state = setup_with(EngineConfig::from_env());
+    state.input_map = default_controls();
+    if let Err(e) = state.input_map.load_overrides("controls.json") {
+        println!("Couldn't read controls.json, using the default controls: {}", e);
+    }
+    // ENGINE_RECORD / ENGINE_REPLAY; before the shuffle, so a replay deals the same cards
+    if let Err(e) = state.record_or_replay_from_env() {
+        println!("Couldn't start recording or replay: {}", e);
+    }
+    deck1.shuffle_with(&mut state.rng);
+    deck2.shuffle_with(&mut state.rng);
+    let (width, height) = state.screen_size();
+    let card_size = (width / 9, height / 6);
     // state.bg_color = BACKGROUND_COLOR;
-    let event_loop = EventLoop::new();
+    // setup_with already made the event loop the window belongs to
+    let event_loop = state.event_loop.take().unwrap();
 
     let mut starting_game_objects: Vec<Drawable> = vec![];
 
@@ -49,8 +66,9 @@ fn main() {
     //     Drawable::RectOutlined(r2, c2, Some(DraggableSnapType::Card(true, false))),
     // ];
 
-    let mut slots = generate_deck_slots(
-        CARD_SIZE,
+    let slots = generate_deck_slots(
+        state.screen_size(),
+        card_size,
         CARD_PADDING_BOTTOM,
         CARD_PADDING_TOP,
         5,
@@ -61,16 +79,15 @@ fn main() {
         true
     );
 
-    let mut battle_slots = generate_battle_slots(
-        CARD_SIZE,
+    let battle_slots = generate_battle_slots(
+        state.screen_size(),
+        card_size,
         CARD_PADDING_BOTTOM,
         CARD_PADDING_TOP,
         5,
-        (255, 0, 0, 0),
+        (255, 0, 0, 255),
     );
 
-    dbg!(slots.len());
-
     //these are the rectangles to fit cards in
     let mut p1_deck_slots = slots[2..22].iter().step_by(4);
     let mut p2_deck_slots = slots[4..24].iter().step_by(4);
@@ -83,19 +100,18 @@ fn main() {
 
     let p1_mana_r = Rect {
         x: p1_d_r.x,
-        y: p1_d_r.y + CARD_PADDING_BOTTOM + CARD_PADDING_TOP + CARD_SIZE.1,
+        y: p1_d_r.y + CARD_PADDING_BOTTOM + CARD_PADDING_TOP + card_size.1,
         w: p1_d_r.w,
         h: p1_d_r.h,
     };
 
     let p2_mana_r = Rect {
         x: p2_d_r.x,
-        y: p2_d_r.y - CARD_PADDING_BOTTOM - CARD_PADDING_TOP - CARD_SIZE.1,
+        y: p2_d_r.y - CARD_PADDING_BOTTOM - CARD_PADDING_TOP - card_size.1,
         w: p2_d_r.w,
         h: p2_d_r.h,
     };
 
-    let mut played_drawables = vec![];
     let mut played_cards: Vec<PlayedCard> = vec![];
 
     for _ in 0..5 {
@@ -103,7 +119,6 @@ fn main() {
 
         let p1card = deck1.draw_and_remove().play(slot.get_rect());
 
-        played_drawables.push(p1card.get_drawable());
         played_cards.push(p1card);
         // dbg!(played_drawables[played_drawables.len() - 1].get_coords());
         // dbg!(slot.get_coords());
@@ -112,121 +127,133 @@ fn main() {
             .draw_and_remove()
             .play(p2_deck_slots.next().unwrap().get_rect());
 
-        played_drawables.push(p2card.get_drawable());
         played_cards.push(p2card);
     }
 
     starting_game_objects.append(&mut slots.clone());
     // starting_game_objects.append(&mut boxes.clone());
-    starting_game_objects.append(&mut played_drawables.clone());
-    starting_game_objects.append(&mut battle_slots.clone());
 
-    state.drawables = starting_game_objects.clone();
-
-    dbg!(state.drawables.len());
-
-    p1_m_idx = state.drawables.len();
-    state
-        .drawables
-        .push(Drawable::Text(p1_mana_r, p1_mana.to_string(), FontFamily::GameTitle, 10.0));
+    state.drawables.extend(starting_game_objects.iter().cloned());
+
+    // cards in hand can be dragged; they alternate p1, p2
+    let mut drag = DragDrop::new();
+    for (i, card) in played_cards.iter().enumerate() {
+        let id = state.drawables.insert(card.get_drawable());
+        drag.set_payload(
+            id,
+            HandCard {
+                player: i % 2 + 1,
+                cost: card.card.playCost,
+            },
+        );
+    }
 
-    dbg!(state.drawables.len());
-    p2_m_idx = state.drawables.len();
-    state
-        .drawables
-        .push(Drawable::Text(p2_mana_r, p2_mana.to_string(), FontFamily::GameTitle, 10.0));
+    // each player plays into the row of battle slots nearer their hand, and
+    // p1's hand is along the top
+    let battle_slots: Vec<(DrawableId, usize)> = battle_slots
+        .into_iter()
+        .map(|slot| {
+            let player = if slot.get_rect().y < height / 2 { 1 } else { 2 };
+            (state.drawables.insert(slot), player)
+        })
+        .collect();
+    let mut occupied_slots: HashSet<DrawableId> = HashSet::new();
+
+    // between the two decks, where nothing else is
+    let mut ui = Ui::new();
+    let end_turn_r = Rect::new(p1_d_r.x, height / 2 - 30, p1_d_r.w, 60);
+
+    // the mana labels live on the UI layer, so cards dragged under them don't cover them
+    let p1_mana_label = state.drawables.insert(
+        Drawable::Text(p1_mana_r, p1_mana.to_string(), FontId::GAME_TITLE, 10.0, mana_text_style())
+            .on_layer(Layer::Ui, 0),
+    );
 
-    dbg!(state.drawables.len());
+    let p2_mana_label = state.drawables.insert(
+        Drawable::Text(p2_mana_r, p2_mana.to_string(), FontId::GAME_TITLE, 10.0, mana_text_style())
+            .on_layer(Layer::Ui, 0),
+    );
 
     //loop starts here!
 
     event_loop.run(move |event, _, control_flow| {
-        state.drawables.remove(p2_m_idx);
-        state.drawables.remove(p1_m_idx - 1);
-
-        p1_m_idx = state.drawables.len();
-        state.drawables.push(Drawable::Text(
-            p1_mana_r,
-            format!("Player 1 has {} mana", p1_mana),
-            FontFamily::GameTitle,
-            40.0
-        ));
-
-        p2_m_idx = state.drawables.len();
-        state.drawables.push(Drawable::Text(
-            p2_mana_r,
-            format!("Player 2 has {} mana", p2_mana),
-            FontFamily::GameTitle,
-            40.0
-        ));
+        state.drawables.replace(
+            p1_mana_label,
+            Drawable::Text(
+                p1_mana_r,
+                format!("Player 1 has {} mana", p1_mana),
+                FontId::GAME_TITLE,
+                40.0,
+                mana_text_style(),
+            )
+            .on_layer(Layer::Ui, 0),
+        );
+
+        state.drawables.replace(
+            p2_mana_label,
+            Drawable::Text(
+                p2_mana_r,
+                format!("Player 2 has {} mana", p2_mana),
+                FontId::GAME_TITLE,
+                40.0,
+                mana_text_style(),
+            )
+            .on_layer(Layer::Ui, 0),
+        );
 
         match event {
             Event::MainEventsCleared => {
                 state.bg_color = BACKGROUND_COLOR;
 
-                check_and_handle_drag(&mut state);
-                draw(&mut state);
-            }
-            Event::WindowEvent {
-                // Note this deeply nested pattern match
-                event:
-                    WindowEvent::KeyboardInput {
-                        input:
-                            winit::event::KeyboardInput {
-                                // Which serves to filter out only events we actually want
-                                virtual_keycode: Some(VirtualKeyCode::Space),
-                                state: key_state,
-                                ..
-                            },
-                        ..
-                    },
-                ..
-            } => {
-                // It also binds these handy variable names!
-                if key_state == winit::event::ElementState::Pressed {
-                    // VirtualKeycode is an enum with a defined representation
-                    // state.now_keys[virtual_keycode as usize] = true;
+                ui.begin(&mut state);
+                let end_turn_label = format!("End P{}'s turn", turn % 2 + 1);
+                let end_turn = ui.button(&mut state, end_turn_r, &end_turn_label);
+
+                if end_turn.clicked || state.action_just_pressed("end_turn") {
                     turn += 1;
-                    if turn % 2 == 0 {
+                    if turn.is_multiple_of(2) {
                         //next turn, gain more mana
                         p1_mana += 5;
                         p2_mana += 5;
                     }
                 }
-            }
-            Event::WindowEvent {
-                // Note this deeply nested pattern match
-                event:
-                    WindowEvent::KeyboardInput {
-                        input:
-                            winit::event::KeyboardInput {
-                                // Which serves to filter out only events we actually want
-                                virtual_keycode: Some(VirtualKeyCode::Down),
-                                state: key_state,
-                                ..
-                            },
-                        ..
-                    },
-                ..
-            } => {
-                // It also binds these handy variable names!
-                if key_state == winit::event::ElementState::Pressed {
-                    if turn % 2 == 0 {
-                        if p1_mana > 0 {
-                            p1_mana -= 1;
-                        } else {
-                            p1_mana = 0;
-                        }
+
+                if state.action_just_pressed("spend_mana") {
+                    if turn.is_multiple_of(2) {
+                        p1_mana = p1_mana.saturating_sub(1);
                     } else {
-                        if p2_mana > 0 {
-                            p2_mana -= 1;
+                        p2_mana = p2_mana.saturating_sub(1);
+                    }
+                }
+
+                // a card can go into an empty slot on its own side, on its
+                // player's turn, if they can pay for it
+                let whose_turn = if turn.is_multiple_of(2) { 1 } else { 2 };
+                let mana = if whose_turn == 1 { p1_mana } else { p2_mana };
+                let events = if ui.wants_mouse() && drag.dragging().is_none() {
+                    // the click is the button's
+                    vec![]
+                } else {
+                    drag.update(&mut state, |_, _, slot, card: &HandCard| {
+                        card.player == whose_turn
+                            && card.cost <= mana
+                            && !occupied_slots.contains(&slot)
+                            && battle_slots.contains(&(slot, card.player))
+                    })
+                };
+                for event in events {
+                    if let DragEvent::Drop(card_id, slot, card) = event {
+                        if card.player == 1 {
+                            p1_mana -= card.cost;
                         } else {
-                            p2_mana = 0;
+                            p2_mana -= card.cost;
                         }
+                        // it's in play now, so it stays in the slot
+                        occupied_slots.insert(slot);
+                        drag.remove_payload(card_id);
                     }
-
-                    // state.drawables
                 }
+                draw(&mut state);
             }
             _ => handle_winit_event(event, control_flow, &mut state),
         }
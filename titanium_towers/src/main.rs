@@ -1,17 +1,13 @@
 use engine::{
     clear, draw, generate_deck_slots, get_slot_rect, handle_winit_event, load_cards_from_file,
-    move_unit, render_character, setup, Color, DraggableSnapType, Drawable, Event, FontFamily,
-    Rect, VirtualKeyCode, WindowEvent,
+    measure_text, move_unit, render_character, setup_with, Binding, Color, DraggableSnapType,
+    Drawable, EngineConfig, Event, FontId, HAlign, InputMap, Layer, Rect, State, TextStyle, VAlign,
+    VirtualKeyCode, WindowEvent, Wrap,
 };
 use std::time::{Duration, Instant};
 use winit::event_loop::EventLoop;
 
 
-const WIDTH: usize = 1920;
-const HEIGHT: usize = 1080;
-
-const CARD_WIDTH: usize = WIDTH / 10;
-const CARD_HEIGHT: usize = HEIGHT / 6;
 const CARD_PADDING_TOP: usize = 5;
 const CARD_PADDING_BOTTOM: usize = 5;
 const NUM_SLOTS: usize = 4;
@@ -40,17 +36,17 @@ struct TowerTime {
     tower: usize,
     time: Instant,
 }
-fn attack_tower(unit: engine::Unit) -> TowerTime {
+fn attack_tower(unit: engine::Unit, now: Instant) -> TowerTime {
     let c = unit.played_card.card;
     let last_attack_time = unit.t;
     let dmg = c.attack;
     let attack_speed = c.attackSpeed;
-    if last_attack_time.elapsed() >= Duration::from_millis(attack_speed) {
+    if now.duration_since(last_attack_time) >= Duration::from_millis(attack_speed) {
         dbg!(c.name.clone());
         dbg!(dmg);
         return TowerTime {
             tower: dmg,
-            time: Instant::now(),
+            time: now,
         };
     }
 
@@ -60,28 +56,65 @@ fn attack_tower(unit: engine::Unit) -> TowerTime {
     }
 }
 
-fn ready_to_play(t: Instant, card_cost: usize) -> bool {
-    t.elapsed() >= Duration::from_secs(card_cost as u64)
+// Text in the game title font, in a rect measured to fit it and centered on
+// center. Anything wider than max_width wraps.
+fn centered_text(
+    state: &State,
+    s: String,
+    center: (usize, usize),
+    size: f32,
+    max_width: Option<usize>,
+    style: TextStyle,
+) -> Drawable {
+    let metrics = measure_text(&state.fonts, &s, FontId::GAME_TITLE, size, max_width, &style);
+    let r = metrics.rect_centered_on(center.0, center.1);
+    Drawable::Text(r, s, FontId::GAME_TITLE, size, style)
+}
+
+fn mana_text_style() -> TextStyle {
+    TextStyle::new((120, 180, 255, 255))
+        .with_shadow((0, 0, 0, 160), (2, 2))
+        .aligned(HAlign::Center, VAlign::Middle)
+        .with_wrap(Wrap::Off)
 }
 
-fn generate_health_bar(hp: usize, tower: usize) -> Vec<Drawable> {
+// 1-4 play player 1's cards and 7-0 player 2's, unless controls.json says otherwise.
+fn default_controls() -> InputMap {
+    InputMap::new()
+        .with("p1_play_1", &[Binding::Key(VirtualKeyCode::Key1)])
+        .with("p1_play_2", &[Binding::Key(VirtualKeyCode::Key2)])
+        .with("p1_play_3", &[Binding::Key(VirtualKeyCode::Key3)])
+        .with("p1_play_4", &[Binding::Key(VirtualKeyCode::Key4)])
+        .with("p2_play_1", &[Binding::Key(VirtualKeyCode::Key7)])
+        .with("p2_play_2", &[Binding::Key(VirtualKeyCode::Key8)])
+        .with("p2_play_3", &[Binding::Key(VirtualKeyCode::Key9)])
+        .with("p2_play_4", &[Binding::Key(VirtualKeyCode::Key0)])
+}
+
+// now is state.now(), so replays see the same mana as the recording did
+fn ready_to_play(now: Instant, t: Instant, card_cost: usize) -> bool {
+    now.duration_since(t) >= Duration::from_secs(card_cost as u64)
+}
+
+fn generate_health_bar(hp: usize, tower: usize, screen_size: (usize, usize)) -> Vec<Drawable> {
+    let (width, height) = screen_size;
     let remaining_health_width = ((hp as f32 / TOWER_START_HP as f32) * 200.0) as usize;
-    let tower_x = if tower == 1 { 100 } else { WIDTH - 300 };
+    let tower_x = if tower == 1 { 100 } else { width - 300 };
     let r1 = Rect {
         x: tower_x,
-        y: HEIGHT / 2 - 70,
+        y: height / 2 - 70,
         w: remaining_health_width,
         h: 10,
     };
     let r2 = Rect {
         x: tower_x + remaining_health_width,
-        y: HEIGHT / 2 - 70,
+        y: height / 2 - 70,
         w: 200 - remaining_health_width,
         h: 10,
     };
 
-    let red = (255, 0, 0, 0);
-    let green = (0, 255, 0, 0);
+    let red = (255, 0, 0, 255);
+    let green = (0, 255, 0, 255);
 
     vec![
         Drawable::Rectangle(r1, green, None),
@@ -90,6 +123,21 @@ fn generate_health_bar(hp: usize, tower: usize) -> Vec<Drawable> {
 }
 
 fn main() {
+    // ENGINE_RESOLUTION=1280x720 picks the framebuffer size; everything below scales with it
+    let mut state = setup_with(EngineConfig::from_env());
+    state.input_map = default_controls();
+    if let Err(e) = state.input_map.load_overrides("controls.json") {
+        println!("Couldn't read controls.json, using the default controls: {}", e);
+    }
+    // ENGINE_RECORD / ENGINE_REPLAY; before the shuffle, so a replay deals the same cards
+    if let Err(e) = state.record_or_replay_from_env() {
+        println!("Couldn't start recording or replay: {}", e);
+    }
+    let event_loop = state.event_loop.take().unwrap();
+    let screen_size = state.screen_size();
+    let (width, height) = screen_size;
+    let card_size = (width / 10, height / 6);
+
     let mut game_state = GameState::Started;
 
     let mut tower1_hp = TOWER_START_HP;
@@ -97,27 +145,27 @@ fn main() {
 
     let r1 = Rect {
         x: 100,
-        y: HEIGHT / 2 - 50,
+        y: height / 2 - 50,
         w: 200,
         h: 200,
     };
     let r2 = Rect {
-        x: WIDTH - 300,
-        y: HEIGHT / 2 - 50,
+        x: width - 300,
+        y: height / 2 - 50,
         w: 200,
         h: 200,
     };
 
     let spawn1 = Rect {
         x: 300,
-        y: HEIGHT / 2 - 50,
+        y: height / 2 - 50,
         w: 20,
         h: 20,
     };
 
     let spawn2 = Rect {
-        x: WIDTH - 300,
-        y: HEIGHT / 2 - 50,
+        x: width - 300,
+        y: height / 2 - 50,
         w: 20,
         h: 20,
     };
@@ -125,11 +173,8 @@ fn main() {
     let mut unit_id = 0;
 
     let mut deck = load_cards_from_file("../cards2.json");
-    let c1 = (0, 0, 255, 0);
-    let c2 = (255, 255, 0, 0);
-
-    let mut state = setup();
-    let event_loop = state.event_loop;
+    let c1 = (0, 0, 255, 255);
+    let c2 = (255, 255, 0, 255);
 
     let mut starting_game_objects: Vec<Drawable> = vec![];
 
@@ -138,11 +183,12 @@ fn main() {
         Drawable::Rectangle(r2, c2, Some(DraggableSnapType::Card(false, false))),
     ];
 
-    let mut p1_last_played_t = Instant::now();
-    let mut p2_last_played_t = Instant::now();
+    let mut p1_last_played_t = state.now();
+    let mut p2_last_played_t = state.now();
 
     let mut slots = generate_deck_slots(
-        (CARD_WIDTH, CARD_HEIGHT),
+        screen_size,
+        card_size,
         CARD_PADDING_BOTTOM,
         CARD_PADDING_TOP,
         NUM_SLOTS,
@@ -153,45 +199,49 @@ fn main() {
         false
     );
 
-    deck.shuffle();
+    deck.shuffle_with(&mut state.rng);
     let mut card1 = deck.draw_and_cycle();
     let mut card2 = deck.draw_and_cycle();
     let mut card3 = deck.draw_and_cycle();
     let mut card4 = deck.draw_and_cycle();
     let played_card1 = card1.play(get_slot_rect(
+        screen_size,
         1,
-        (CARD_WIDTH, CARD_HEIGHT),
+        card_size,
         NUM_SLOTS,
         false,
         CARD_PADDING_TOP,
         CARD_PADDING_BOTTOM,
     ));
     let played_card2 = card2.play(get_slot_rect(
+        screen_size,
         2,
-        (CARD_WIDTH, CARD_HEIGHT),
+        card_size,
         NUM_SLOTS,
         false,
         CARD_PADDING_TOP,
         CARD_PADDING_BOTTOM,
     ));
     let played_card3 = card3.play(get_slot_rect(
+        screen_size,
         3,
-        (CARD_WIDTH, CARD_HEIGHT),
+        card_size,
         NUM_SLOTS,
         false,
         CARD_PADDING_TOP,
         CARD_PADDING_BOTTOM,
     ));
     let played_card4 = card4.play(get_slot_rect(
+        screen_size,
         4,
-        (CARD_WIDTH, CARD_HEIGHT),
+        card_size,
         NUM_SLOTS,
         false,
         CARD_PADDING_TOP,
         CARD_PADDING_BOTTOM,
     ));
 
-    let mut played_drawable = vec![
+    let played_drawable = vec![
         played_card1.get_clash_drawable(),
         played_card2.get_clash_drawable(),
         played_card3.get_clash_drawable(),
@@ -202,60 +252,59 @@ fn main() {
     starting_game_objects.append(&mut towers);
     // starting_game_objects.append(&mut played_drawable);
 
-    state.drawables = starting_game_objects.clone();
-    state.drawables.append(&mut played_drawable);
+    state.drawables.extend(starting_game_objects.iter().cloned());
+    state.drawables.extend(played_drawable);
 
     event_loop.run(move |event, _, control_flow| {
         if event == Event::MainEventsCleared {
+            let now = state.now();
             if game_state == GameState::P1Won {
                 let result_string = "Player 2 has fallen. Player 1 Wins!";
-                let result_text = Drawable::Text(
-                    Rect {
-                        x: 30,
-                        y: 30,
-                        w: WIDTH - 30,
-                        h: 200,
-                    },
+                let result_text = centered_text(
+                    &state,
                     result_string.to_string(),
-                    FontFamily::GameTitle,
+                    (width / 2, 130),
                     100.0,
+                    Some(width - 60),
+                    TextStyle::new((255, 255, 255, 255))
+                        .with_outline((0, 0, 0, 255), 3)
+                        .aligned(HAlign::Center, VAlign::Middle),
                 );
-                state.drawables.push(result_text);
+                state.drawables.insert(result_text.on_layer(Layer::Overlay, 0));
                 draw(&mut state);
                 return;
             } else if game_state == GameState::P2Won {
                 let result_string = "Player 1 has fallen. Player 2 Wins!";
-                let result_text = Drawable::Text(
-                    Rect {
-                        x: 30,
-                        y: 30,
-                        w: WIDTH - 30,
-                        h: 200,
-                    },
+                let result_text = centered_text(
+                    &state,
                     result_string.to_string(),
-                    FontFamily::GameTitle,
+                    (width / 2, 130),
                     100.0,
+                    Some(width - 60),
+                    TextStyle::new((255, 255, 255, 255))
+                        .with_outline((0, 0, 0, 255), 3)
+                        .aligned(HAlign::Center, VAlign::Middle),
                 );
-                state.drawables.push(result_text);
+                state.drawables.insert(result_text.on_layer(Layer::Overlay, 0));
                 draw(&mut state);
                 return;
             }
 
-            if state.now_keys[VirtualKeyCode::Key1 as usize]
-                && !state.prev_keys[VirtualKeyCode::Key1 as usize]
-                && ready_to_play(p1_last_played_t, card1.playCost)
+            if state.action_just_pressed("p1_play_1")
+                && ready_to_play(now, p1_last_played_t, card1.playCost)
             {
                 let hp = card1.health;
                 let played_card1 = card1.play(get_slot_rect(
+                    screen_size,
                     1,
-                    (CARD_WIDTH, CARD_HEIGHT),
+                    card_size,
                     NUM_SLOTS,
                     false,
                     CARD_PADDING_TOP,
                     CARD_PADDING_BOTTOM,
                 ));
                 let u = played_card1.play_unit(
-                    std::time::Instant::now(),
+                    now,
                     hp,
                     create_spawn_point(spawn1, unit_id),
                 );
@@ -269,21 +318,21 @@ fn main() {
                 card1 = deck.draw_and_cycle();
             }
 
-            if state.now_keys[VirtualKeyCode::Key2 as usize]
-                && !state.prev_keys[VirtualKeyCode::Key2 as usize]
-                && ready_to_play(p1_last_played_t, card2.playCost)
+            if state.action_just_pressed("p1_play_2")
+                && ready_to_play(now, p1_last_played_t, card2.playCost)
             {
                 let hp = card2.health;
                 let played_card2 = card2.play(get_slot_rect(
+                    screen_size,
                     2,
-                    (CARD_WIDTH, CARD_HEIGHT),
+                    card_size,
                     NUM_SLOTS,
                     false,
                     CARD_PADDING_TOP,
                     CARD_PADDING_BOTTOM,
                 ));
                 let u = played_card2.play_unit(
-                    std::time::Instant::now(),
+                    now,
                     hp,
                     create_spawn_point(spawn1, unit_id),
                 );
@@ -296,21 +345,21 @@ fn main() {
                 card2 = deck.draw_and_cycle();
             }
 
-            if state.now_keys[VirtualKeyCode::Key3 as usize]
-                && !state.prev_keys[VirtualKeyCode::Key3 as usize]
-                && ready_to_play(p1_last_played_t, card3.playCost)
+            if state.action_just_pressed("p1_play_3")
+                && ready_to_play(now, p1_last_played_t, card3.playCost)
             {
                 let hp = card3.health;
                 let played_card3 = card3.play(get_slot_rect(
+                    screen_size,
                     3,
-                    (CARD_WIDTH, CARD_HEIGHT),
+                    card_size,
                     NUM_SLOTS,
                     false,
                     CARD_PADDING_TOP,
                     CARD_PADDING_BOTTOM,
                 ));
                 let u = played_card3.play_unit(
-                    std::time::Instant::now(),
+                    now,
                     hp,
                     create_spawn_point(spawn1, unit_id),
                 );
@@ -323,21 +372,21 @@ fn main() {
                 card3 = deck.draw_and_cycle();
             }
 
-            if state.now_keys[VirtualKeyCode::Key4 as usize]
-                && !state.prev_keys[VirtualKeyCode::Key4 as usize]
-                && ready_to_play(p1_last_played_t, card4.playCost)
+            if state.action_just_pressed("p1_play_4")
+                && ready_to_play(now, p1_last_played_t, card4.playCost)
             {
                 let hp = card4.health;
                 let played_card4 = card4.play(get_slot_rect(
+                    screen_size,
                     4,
-                    (CARD_WIDTH, CARD_HEIGHT),
+                    card_size,
                     NUM_SLOTS,
                     false,
                     CARD_PADDING_TOP,
                     CARD_PADDING_BOTTOM,
                 ));
                 let u = played_card4.play_unit(
-                    std::time::Instant::now(),
+                    now,
                     hp,
                     create_spawn_point(spawn1, unit_id),
                 );
@@ -350,21 +399,21 @@ fn main() {
                 card4 = deck.draw_and_cycle();
             }
 
-            if state.now_keys[VirtualKeyCode::Key7 as usize]
-                && !state.prev_keys[VirtualKeyCode::Key7 as usize]
-                && ready_to_play(p2_last_played_t, card1.playCost)
+            if state.action_just_pressed("p2_play_1")
+                && ready_to_play(now, p2_last_played_t, card1.playCost)
             {
                 let hp = card1.health;
                 let played_card1 = card1.play(get_slot_rect(
+                    screen_size,
                     5,
-                    (CARD_WIDTH, CARD_HEIGHT),
+                    card_size,
                     NUM_SLOTS,
                     true,
                     CARD_PADDING_TOP,
                     CARD_PADDING_BOTTOM,
                 ));
                 let u = played_card1.play_unit(
-                    std::time::Instant::now(),
+                    now,
                     hp,
                     create_spawn_point(spawn2, unit_id),
                 );
@@ -377,21 +426,21 @@ fn main() {
                 card1 = deck.draw_and_cycle();
             }
 
-            if state.now_keys[VirtualKeyCode::Key8 as usize]
-                && !state.prev_keys[VirtualKeyCode::Key8 as usize]
-                && ready_to_play(p2_last_played_t, card2.playCost)
+            if state.action_just_pressed("p2_play_2")
+                && ready_to_play(now, p2_last_played_t, card2.playCost)
             {
                 let hp = card2.health;
                 let played_card2 = card2.play(get_slot_rect(
+                    screen_size,
                     6,
-                    (CARD_WIDTH, CARD_HEIGHT),
+                    card_size,
                     NUM_SLOTS,
                     true,
                     CARD_PADDING_TOP,
                     CARD_PADDING_BOTTOM,
                 ));
                 let u = played_card2.play_unit(
-                    std::time::Instant::now(),
+                    now,
                     hp,
                     create_spawn_point(spawn2, unit_id),
                 );
@@ -404,21 +453,21 @@ fn main() {
                 card2 = deck.draw_and_cycle();
             }
 
-            if state.now_keys[VirtualKeyCode::Key9 as usize]
-                && !state.prev_keys[VirtualKeyCode::Key9 as usize]
-                && ready_to_play(p2_last_played_t, card3.playCost)
+            if state.action_just_pressed("p2_play_3")
+                && ready_to_play(now, p2_last_played_t, card3.playCost)
             {
                 let hp = card3.health;
                 let played_card3 = card3.play(get_slot_rect(
+                    screen_size,
                     7,
-                    (CARD_WIDTH, CARD_HEIGHT),
+                    card_size,
                     NUM_SLOTS,
                     true,
                     CARD_PADDING_TOP,
                     CARD_PADDING_BOTTOM,
                 ));
                 let u = played_card3.play_unit(
-                    std::time::Instant::now(),
+                    now,
                     hp,
                     create_spawn_point(spawn2, unit_id),
                 );
@@ -431,21 +480,21 @@ fn main() {
                 card3 = deck.draw_and_cycle();
             }
 
-            if state.now_keys[VirtualKeyCode::Key0 as usize]
-                && !state.prev_keys[VirtualKeyCode::Key0 as usize]
-                && ready_to_play(p2_last_played_t, card4.playCost)
+            if state.action_just_pressed("p2_play_4")
+                && ready_to_play(now, p2_last_played_t, card4.playCost)
             {
                 let hp = card4.health;
                 let played_card4 = card4.play(get_slot_rect(
+                    screen_size,
                     8,
-                    (CARD_WIDTH, CARD_HEIGHT),
+                    card_size,
                     NUM_SLOTS,
                     false,
                     CARD_PADDING_TOP,
                     CARD_PADDING_BOTTOM,
                 ));
                 let u = played_card4.play_unit(
-                    std::time::Instant::now(),
+                    now,
                     hp,
                     create_spawn_point(spawn2, unit_id),
                 );
@@ -458,11 +507,12 @@ fn main() {
                 card4 = deck.draw_and_cycle();
             }
             // dbg!(&played_card1);
-            let mut cards = vec![
+            let cards = vec![
                 card1
                     .play(get_slot_rect(
+                        screen_size,
                         1,
-                        (CARD_WIDTH, CARD_HEIGHT),
+                        card_size,
                         NUM_SLOTS,
                         false,
                         CARD_PADDING_TOP,
@@ -471,8 +521,9 @@ fn main() {
                     .get_clash_drawable(),
                 card2
                     .play(get_slot_rect(
+                        screen_size,
                         2,
-                        (CARD_WIDTH, CARD_HEIGHT),
+                        card_size,
                         NUM_SLOTS,
                         false,
                         CARD_PADDING_TOP,
@@ -481,8 +532,9 @@ fn main() {
                     .get_clash_drawable(),
                 card3
                     .play(get_slot_rect(
+                        screen_size,
                         3,
-                        (CARD_WIDTH, CARD_HEIGHT),
+                        card_size,
                         NUM_SLOTS,
                         false,
                         CARD_PADDING_TOP,
@@ -491,8 +543,9 @@ fn main() {
                     .get_clash_drawable(),
                 card4
                     .play(get_slot_rect(
+                        screen_size,
                         4,
-                        (CARD_WIDTH, CARD_HEIGHT),
+                        card_size,
                         NUM_SLOTS,
                         false,
                         CARD_PADDING_TOP,
@@ -502,31 +555,25 @@ fn main() {
             ];
 
 
-            let p1_mana = (p1_last_played_t.elapsed().as_secs()).to_string();
-            let p2_mana = (p2_last_played_t.elapsed().as_secs()).to_string();
+            let p1_mana = (now.duration_since(p1_last_played_t).as_secs()).to_string();
+            let p2_mana = (now.duration_since(p2_last_played_t).as_secs()).to_string();
 
-            let mut mana_drawables = vec![
-                Drawable::Text(
-                    Rect {
-                        x: 100,
-                        y: HEIGHT / 2 + 170,
-                        w: 200,
-                        h: 80,
-                    },
-                    format!("Mana: {}",p1_mana).to_string(),
-                    FontFamily::GameTitle,
+            let mana_drawables = vec![
+                centered_text(
+                    &state,
+                    format!("Mana: {}", p1_mana),
+                    (200, height / 2 + 210),
                     40.0,
+                    None,
+                    mana_text_style(),
                 ),
-                Drawable::Text(
-                    Rect {
-                        x: WIDTH - 300,
-                        y: HEIGHT / 2 + 170,
-                        w: 200,
-                        h: 80,
-                    },
-                    format!("Mana: {}",p2_mana),
-                    FontFamily::GameTitle,
+                centered_text(
+                    &state,
+                    format!("Mana: {}", p2_mana),
+                    (width - 200, height / 2 + 210),
                     40.0,
+                    None,
+                    mana_text_style(),
                 ),
             ];
 
@@ -536,12 +583,12 @@ fn main() {
 
             for unit in state.p1_units.iter() {
                 let c = unit.played_card.card.clone();
-                if unit.get_rect_x() <= WIDTH - 300 {
+                if unit.get_rect_x() <= width - 300 {
                     p1_unit_drawables.push(unit.move_unit(c.speed * 3));
                 } else {
                     // take damage
                     // check if dead
-                    let tower_time = attack_tower(unit.get_unit());
+                    let tower_time = attack_tower(unit.get_unit(), now);
                     if tower_time.tower > tower2_hp {
                         tower2_hp = 0;
                         game_state = GameState::P1Won;
@@ -562,7 +609,7 @@ fn main() {
                     // take damage
                     // check if dead
 
-                    let tower_time = attack_tower(unit.get_unit());
+                    let tower_time = attack_tower(unit.get_unit(), now);
 
                     if tower_time.tower > tower1_hp {
                         tower1_hp = 0;
@@ -575,22 +622,24 @@ fn main() {
                     // dbg!(tower1_hp);
                 }
             }
-            state.drawables = starting_game_objects.clone();
-            state.drawables.append(&mut cards);
+            // the board is rebuilt from scratch every frame
+            state.drawables.clear();
+            state.drawables.extend(starting_game_objects.iter().cloned());
+            state.drawables.extend(cards);
             for unit in p1_unit_drawables.iter() {
-                state.drawables.push(unit.played_card.get_drawable_rect(c1));
+                state.drawables.insert(unit.played_card.get_drawable_rect(c1));
             }
 
             for unit in p2_unit_drawables.iter() {
-                state.drawables.push(unit.played_card.get_drawable_rect(c2));
+                state.drawables.insert(unit.played_card.get_drawable_rect(c2));
             }
 
-            state.drawables.append(&mut mana_drawables);
+            state
+                .drawables
+                .extend(mana_drawables.into_iter().map(|d| d.on_layer(Layer::Ui, 0)));
 
-            let mut health_bar_1 = generate_health_bar(tower1_hp, 1);
-            state.drawables.append(&mut health_bar_1);
-            let mut health_bar_2 = generate_health_bar(tower2_hp, 2);
-            state.drawables.append(&mut health_bar_2);
+            state.drawables.extend(generate_health_bar(tower1_hp, 1, screen_size));
+            state.drawables.extend(generate_health_bar(tower2_hp, 2, screen_size));
 
             state.p1_units = p1_unit_drawables;
             state.p2_units = p2_unit_drawables;